@@ -0,0 +1,299 @@
+use crate::chacha;
+use crate::rsa::Error;
+
+/// Length of a ChaCha20-Poly1305 key in bytes
+pub const KEY_SIZE: usize = 32;
+
+/// Length of a ChaCha20-Poly1305 nonce in bytes
+pub const NONCE_SIZE: usize = 12;
+
+/// Length of a Poly1305 tag in bytes
+pub const TAG_SIZE: usize = 16;
+
+/// Poly1305 works on numbers in five 26-bit limbs, so that products of limbs fit in a `u64`
+const LIMB_MASK: u32 = 0x3ff_ffff;
+
+/// Helper function, reads four bytes of `bytes` from `offset` as a little-endian word
+fn _le_word(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// The Poly1305 one-time authenticator (RFC 8439 section 2.5). Evaluates the message, in 16-byte
+/// blocks, as a polynomial at the secret point r modulo 2^130 - 5, and adds the secret s to the
+/// result. A key must never be used for more than one message.
+pub struct Poly1305 {
+    /// r, clamped, in 26-bit limbs
+    r: [u32; 5],
+    /// s, as four little-endian words
+    s: [u32; 4],
+    /// Accumulator, in 26-bit limbs
+    h: [u32; 5],
+    /// Bytes of a partial block left over from the last update
+    buffer: [u8; 16],
+    /// Number of bytes in `buffer`
+    buffered: usize,
+}
+
+impl Poly1305 {
+    /// Returns a new authenticator for the 32-byte one-time key r || s
+    pub fn new(key: &[u8; KEY_SIZE]) -> Poly1305 {
+        // Clamping clears the top four bits of every fourth byte of r and the bottom two bits of
+        // the bytes after them, which the masks do as they split r into limbs
+        Poly1305 {
+            r: [
+                _le_word(key, 0) & 0x3ff_ffff,
+                (_le_word(key, 3) >> 2) & 0x3ff_ff03,
+                (_le_word(key, 6) >> 4) & 0x3ff_c0ff,
+                (_le_word(key, 9) >> 6) & 0x3f0_3fff,
+                (_le_word(key, 12) >> 8) & 0x00f_ffff,
+            ],
+            s: [_le_word(key, 16), _le_word(key, 20), _le_word(key, 24), _le_word(key, 28)],
+            h: [0; 5],
+            buffer: [0; 16],
+            buffered: 0,
+        }
+    }
+
+    /// Helper function, adds the 16-byte `block` to the accumulator and multiplies by r. `high_bit`
+    /// is the bit set just above the block: 2^128 for full blocks, while the last partial block
+    /// has its 1 bit appended as a byte instead.
+    fn _block(&mut self, block: &[u8], high_bit: u32) {
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        // 2^130 = 5 mod 2^130 - 5, so limb products past the top wrap round multiplied by 5
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+
+        let h0 = (self.h[0] + (_le_word(block, 0) & LIMB_MASK)) as u64;
+        let h1 = (self.h[1] + ((_le_word(block, 3) >> 2) & LIMB_MASK)) as u64;
+        let h2 = (self.h[2] + ((_le_word(block, 6) >> 4) & LIMB_MASK)) as u64;
+        let h3 = (self.h[3] + ((_le_word(block, 9) >> 6) & LIMB_MASK)) as u64;
+        let h4 = (self.h[4] + ((_le_word(block, 12) >> 8) | high_bit)) as u64;
+
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        // Carry back down to 26-bit limbs, only partly reducing mod 2^130 - 5
+        d1 += d0 >> 26;
+        d2 += d1 >> 26;
+        d3 += d2 >> 26;
+        d4 += d3 >> 26;
+        let mask = LIMB_MASK as u64;
+        let h0 = (d0 & mask) + (d4 >> 26) * 5;
+        let h1 = (d1 & mask) + (h0 >> 26);
+        self.h = [(h0 & mask) as u32, h1 as u32, (d2 & mask) as u32, (d3 & mask) as u32, (d4 & mask) as u32];
+    }
+
+    /// Feeds `data` into the authenticator
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.buffered > 0 {
+            let taken = data.len().min(16 - self.buffered);
+            self.buffer[self.buffered..self.buffered + taken].copy_from_slice(&data[..taken]);
+            self.buffered += taken;
+            data = &data[taken..];
+            if self.buffered < 16 {
+                return;
+            }
+            let block = self.buffer;
+            self._block(&block, 1 << 24);
+            self.buffered = 0;
+        }
+
+        let mut blocks = data.chunks_exact(16);
+        for block in &mut blocks {
+            self._block(block, 1 << 24);
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    /// Returns the tag for everything fed in
+    pub fn finalize(mut self) -> [u8; TAG_SIZE] {
+        if self.buffered > 0 {
+            let mut block = [0_u8; 16];
+            block[..self.buffered].copy_from_slice(&self.buffer[..self.buffered]);
+            block[self.buffered] = 1;
+            self._block(&block, 0);
+        }
+
+        // Carry fully, then subtract p = 2^130 - 5 if h >= p, choosing without branching on h
+        let mut h = self.h;
+        for i in 1..5 {
+            h[i] += h[i - 1] >> 26;
+            h[i - 1] &= LIMB_MASK;
+        }
+        h[0] += (h[4] >> 26) * 5;
+        h[4] &= LIMB_MASK;
+        h[1] += h[0] >> 26;
+        h[0] &= LIMB_MASK;
+
+        // g = h + 5 - 2^130, which has its top bit set exactly when h < p
+        let mut g = [0_u32; 5];
+        let mut carry = 5;
+        for i in 0..4 {
+            g[i] = h[i] + carry;
+            carry = g[i] >> 26;
+            g[i] &= LIMB_MASK;
+        }
+        g[4] = (h[4] + carry).wrapping_sub(1 << 26);
+        let use_g = (g[4] >> 31).wrapping_sub(1);
+        for i in 0..5 {
+            h[i] = (h[i] & !use_g) | (g[i] & use_g);
+        }
+
+        // Pack into 128 bits and add s mod 2^128
+        let words = [
+            h[0] | (h[1] << 26),
+            (h[1] >> 6) | (h[2] << 20),
+            (h[2] >> 12) | (h[3] << 14),
+            (h[3] >> 18) | (h[4] << 8),
+        ];
+        let mut tag = [0_u8; TAG_SIZE];
+        let mut sum = 0_u64;
+        for i in 0..4 {
+            sum = words[i] as u64 + self.s[i] as u64 + (sum >> 32);
+            tag[4 * i..4 * i + 4].copy_from_slice(&(sum as u32).to_le_bytes());
+        }
+        tag
+    }
+}
+
+/// Helper function, converts a byte key and nonce to the words ChaCha20 takes
+fn _words(key: &[u8; KEY_SIZE], nonce: &[u8; NONCE_SIZE]) -> ([u32; 8], [u32; 3]) {
+    let mut key_words = [0_u32; 8];
+    let mut nonce_words = [0_u32; 3];
+    chacha::le_words(key, &mut key_words);
+    chacha::le_words(nonce, &mut nonce_words);
+    (key_words, nonce_words)
+}
+
+/// Helper function, returns the ChaCha20-Poly1305 tag (RFC 8439 section 2.8) for `aad` and `cipher`.
+/// The one-time Poly1305 key is the start of keystream block 0, which encryption skips.
+fn _tag(key: &[u32; 8], nonce: &[u32; 3], aad: &[u8], cipher: &[u8]) -> [u8; TAG_SIZE] {
+    let block = chacha::chacha20_block(key, 0, nonce);
+    let mut one_time_key = [0_u8; KEY_SIZE];
+    for (bytes, word) in one_time_key.chunks_exact_mut(4).zip(block.iter()) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+
+    // aad || pad || cipher || pad || len(aad) || len(cipher), padding each to a multiple of 16 bytes
+    let zeros = [0_u8; 16];
+    let mut mac = Poly1305::new(&one_time_key);
+    mac.update(aad);
+    mac.update(&zeros[..(16 - aad.len() % 16) % 16]);
+    mac.update(cipher);
+    mac.update(&zeros[..(16 - cipher.len() % 16) % 16]);
+    mac.update(&(aad.len() as u64).to_le_bytes());
+    mac.update(&(cipher.len() as u64).to_le_bytes());
+    mac.finalize()
+}
+
+/// Encrypts and authenticates `plaintext` with ChaCha20-Poly1305 (RFC 8439 section 2.8), also
+/// authenticating `aad` without encrypting it. Returns the cipher followed by the tag. A nonce must
+/// never be used twice with the same key.
+///
+/// # Arguments
+/// * `key` - 256-bit key
+/// * `nonce` - 96-bit nonce, unique for each message under `key`
+/// * `aad` - Additional data to authenticate, e.g. a header sent in the clear
+/// * `plaintext` - Bytes to encrypt, at most 256 GB
+pub fn seal(key: &[u8; KEY_SIZE], nonce: &[u8; NONCE_SIZE], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let (key, nonce) = _words(key, nonce);
+    let mut sealed = plaintext.to_vec();
+    chacha::chacha20_xor(&key, 1, &nonce, &mut sealed);
+
+    let tag = _tag(&key, &nonce, aad, &sealed);
+    sealed.extend_from_slice(&tag);
+    sealed
+}
+
+/// Checks and decrypts a cipher produced by `seal`. Fails with `Error::Decryption` if the tag
+/// doesn't match, i.e. if the key, nonce or additional data are wrong or anything was altered.
+///
+/// # Arguments
+/// * `key` - 256-bit key
+/// * `nonce` - Nonce used when sealing
+/// * `aad` - Additional data given when sealing
+/// * `sealed` - Cipher followed by tag
+pub fn open(key: &[u8; KEY_SIZE], nonce: &[u8; NONCE_SIZE], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    if sealed.len() < TAG_SIZE {
+        return Err(Error::Decryption);
+    }
+    let (key, nonce) = _words(key, nonce);
+    let (cipher, tag) = sealed.split_at(sealed.len() - TAG_SIZE);
+
+    // Compare every byte of the tag so the time taken doesn't show how much of it matched
+    let expected = _tag(&key, &nonce, aad, cipher);
+    let difference = expected.iter().zip(tag).fold(0_u8, |difference, (a, b)| difference | (a ^ b));
+    if difference != 0 {
+        return Err(Error::Decryption);
+    }
+
+    let mut plaintext = cipher.to_vec();
+    chacha::chacha20_xor(&key, 1, &nonce, &mut plaintext);
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a hex string into bytes
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// Key, nonce, additional data and plaintext of RFC 8439 section 2.8.2
+    fn rfc_inputs() -> ([u8; KEY_SIZE], [u8; NONCE_SIZE], Vec<u8>, Vec<u8>) {
+        let mut key = [0_u8; KEY_SIZE];
+        key.copy_from_slice(&hex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f"));
+        let mut nonce = [0_u8; NONCE_SIZE];
+        nonce.copy_from_slice(&hex("070000004041424344454647"));
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the \
+                          future, sunscreen would be it.".to_vec();
+        (key, nonce, aad, plaintext)
+    }
+
+    #[test]
+    fn poly1305_matches_rfc_8439() {
+        let mut key = [0_u8; KEY_SIZE];
+        key.copy_from_slice(&hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b"));
+        let mut mac = Poly1305::new(&key);
+        mac.update(b"Cryptographic Forum Research Group");
+        assert_eq!(mac.finalize().to_vec(), hex("a8061dc1305136c6c22b8baf0c0127a9"));
+    }
+
+    #[test]
+    fn seal_matches_rfc_8439() {
+        let (key, nonce, aad, plaintext) = rfc_inputs();
+        let sealed = seal(&key, &nonce, &aad, &plaintext);
+
+        let mut expected = hex("d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb\
+                                69da92728b1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fa\
+                                d675945585808b4831d7bc3ff4def08e4b7a9de576d26586cec64b6116");
+        expected.extend(hex("1ae10b594f09e26a7e902ecbd0600691"));
+        assert_eq!(sealed, expected);
+        assert_eq!(open(&key, &nonce, &aad, &sealed), Ok(plaintext));
+    }
+
+    #[test]
+    fn open_rejects_altered_input() {
+        let (key, nonce, aad, plaintext) = rfc_inputs();
+        let sealed = seal(&key, &nonce, &aad, &plaintext);
+
+        // A flipped byte of the cipher, and of the tag
+        for i in [0, plaintext.len() - 1, plaintext.len(), sealed.len() - 1].iter() {
+            let mut altered = sealed.clone();
+            altered[*i] ^= 1;
+            assert_eq!(open(&key, &nonce, &aad, &altered), Err(Error::Decryption));
+        }
+
+        let mut altered_aad = aad.clone();
+        altered_aad[0] ^= 1;
+        assert_eq!(open(&key, &nonce, &altered_aad, &sealed), Err(Error::Decryption));
+        assert_eq!(open(&key, &nonce, &aad, &sealed[..TAG_SIZE - 1]), Err(Error::Decryption));
+    }
+}
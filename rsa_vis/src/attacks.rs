@@ -0,0 +1,265 @@
+use num::{BigInt, BigUint, Integer, ToPrimitive};
+use num::bigint::Sign;
+use num::traits::{One, Pow, Signed, Zero};
+
+use crate::rsa;
+use crate::rsa::{RsaPrivateKey, RsaPublicKey};
+
+/// Helper function, returns the private key for public key `key` with prime factors `p` and `q`, or
+/// `None` if `e` has no inverse mod lambda(n)
+fn _private_key(key: &RsaPublicKey, p: BigUint, q: BigUint) -> Option<RsaPrivateKey> {
+    let one: BigUint = One::one();
+    let lambda_n = (&p - &one).lcm(&(&q - &one));
+    if !key.e.gcd(&lambda_n).is_one() {
+        return None;
+    }
+
+    let d = rsa::_modular_multiplicative_inverse(key.e.clone(), lambda_n);
+    Some(RsaPrivateKey::new(key.n.clone(), key.e.clone(), d, Some(p), Some(q)))
+}
+
+/// Helper function, checks one convergent k/d of e/n for Wiener's attack. If d is the private
+/// exponent then phi(n) = (ed - 1) / k, and p and q are the roots of x^2 - (n - phi(n) + 1)x + n.
+fn _wiener_candidate(key: &RsaPublicKey, k: &BigUint, d: &BigUint) -> Option<RsaPrivateKey> {
+    if k.is_zero() {
+        return None;
+    }
+    let (phi, remainder) = (&key.e * d - 1_u32).div_rem(k);
+    if !remainder.is_zero() || phi >= key.n {
+        return None;
+    }
+
+    // p + q = n - phi(n) + 1, and (p - q)^2 = (p + q)^2 - 4n
+    let sum = &key.n - &phi + 1_u32;
+    let sum_squared = &sum * &sum;
+    let four_n = &key.n << 2;
+    if sum_squared < four_n {
+        return None;
+    }
+    let difference = (&sum_squared - &four_n).sqrt();
+    if &difference * &difference != &sum_squared - &four_n {
+        return None;
+    }
+
+    let p = (&sum + &difference) >> 1;
+    let q = (&sum - &difference) >> 1;
+    if &p * &q != key.n {
+        return None;
+    }
+    Some(RsaPrivateKey::new(key.n.clone(), key.e.clone(), d.clone(), Some(p), Some(q)))
+}
+
+/// Recovers the private key from a public key whose private exponent is small, by Wiener's attack.
+/// When d < n^(1/4) / 3, k/d for the k with ed = 1 + k phi(n) is one of the convergents of the
+/// continued fraction of e/n, so trying each convergent in turn finds d and factors `n`. Returns
+/// `None` if no convergent works, which means d is too big for the attack.
+///
+/// # Arguments
+/// * `key` - Public key to attack
+pub fn wiener(key: &RsaPublicKey) -> Option<RsaPrivateKey> {
+    // Convergents h/k of e/n from the continued fraction terms a, with h = a h' + h'' and likewise k
+    let (mut numerator, mut denominator) = (key.e.clone(), key.n.clone());
+    let (mut h, mut h_previous): (BigUint, BigUint) = (One::one(), Zero::zero());
+    let (mut k, mut k_previous): (BigUint, BigUint) = (Zero::zero(), One::one());
+    while !denominator.is_zero() {
+        let (a, remainder) = numerator.div_rem(&denominator);
+        numerator = denominator;
+        denominator = remainder;
+
+        let h_next = &a * &h + &h_previous;
+        let k_next = &a * &k + &k_previous;
+        h_previous = h;
+        h = h_next;
+        k_previous = k;
+        k = k_next;
+
+        if let Some(private_key) = _wiener_candidate(key, &h, &k) {
+            return Some(private_key);
+        }
+    }
+    None
+}
+
+/// Recovers a message block sent unpadded to several recipients who share the small public
+/// exponent e, by Håstad's broadcast attack. Given e of the ciphers, the Chinese Remainder Theorem
+/// gives m^e mod the product of the moduli; since m is less than every modulus, m^e is smaller
+/// than that product, so m is its integer e-th root. Returns `None` if there are fewer than e keys,
+/// e is less than 2, the keys have different exponents, their moduli aren't pairwise coprime, or
+/// the root isn't exact (the messages differed, or were padded).
+///
+/// # Arguments
+/// * `keys` - Public keys the block was encrypted with
+/// * `ciphers` - The block encrypted with each key in `keys`, in the same order
+pub fn hastad_broadcast_block(keys: &[RsaPublicKey], ciphers: &[BigUint]) -> Option<BigUint> {
+    let e = keys.first()?.e.to_u32()?;
+    if e < 2 || keys.len() != ciphers.len() || keys.len() < e as usize
+        || keys.iter().any(|key| key.e != keys[0].e) {
+        return None;
+    }
+    let keys = &keys[..e as usize];
+
+    let product: BigUint = keys.iter().map(|key| &key.n).product();
+    let mut combined: BigUint = Zero::zero();
+    for (key, cipher) in keys.iter().zip(ciphers) {
+        let others = &product / &key.n;
+        let others_mod_n = &others % &key.n;
+        if !others_mod_n.gcd(&key.n).is_one() {
+            return None;
+        }
+        let inverse = rsa::_modular_multiplicative_inverse(others_mod_n, key.n.clone());
+        combined += (cipher % &key.n) * inverse * others;
+    }
+    let combined = combined % &product;
+
+    let m = combined.nth_root(e);
+    if m.pow(e) == combined { Some(m) } else { None }
+}
+
+/// Recovers a string encrypted with `rsa::encrypt_str` under several public keys with the same
+/// small exponent e, applying `hastad_broadcast_block` to each block. Returns `None` if there are
+/// no keys, the ciphers have different numbers of blocks, any block can't be recovered, or the
+/// keys' moduli are of different lengths, which splits the string into different blocks for each.
+///
+/// # Arguments
+/// * `keys` - Public keys the string was encrypted with, at least e of them
+/// * `ciphers` - The cipher for each key in `keys`, in the same order
+pub fn hastad_broadcast(keys: &[RsaPublicKey], ciphers: &[Vec<BigUint>]) -> Option<String> {
+    let capacity = keys.first()?.size().checked_sub(1)?;
+    let blocks = ciphers.first()?.len();
+    if ciphers.iter().any(|cipher| cipher.len() != blocks) {
+        return None;
+    }
+
+    let packed = (0..blocks)
+        .map(|i| {
+            let block: Vec<BigUint> = ciphers.iter().map(|cipher| cipher[i].clone()).collect();
+            hastad_broadcast_block(keys, &block)
+        })
+        .collect::<Option<Vec<BigUint>>>()?;
+    let msg = rsa::_unpack_bytes(&packed, capacity)?;
+    String::from_utf8(msg).ok()
+}
+
+/// Helper function, returns `base^exponent` mod `n` for a possibly negative `exponent`, or `None` if
+/// the exponent is negative and `base` has no inverse mod `n`
+fn _signed_pow_mod(base: &BigUint, exponent: &BigInt, n: &BigUint) -> Option<BigUint> {
+    let magnitude = exponent.abs().to_biguint()?;
+    if exponent.is_negative() {
+        let base = base % n;
+        if !base.gcd(n).is_one() {
+            return None;
+        }
+        let inverse = rsa::_modular_multiplicative_inverse(base, n.clone());
+        Some(inverse.modpow(&magnitude, n))
+    } else {
+        Some(base.modpow(&magnitude, n))
+    }
+}
+
+/// Recovers a message block encrypted under the same modulus with two coprime public exponents, by
+/// the common modulus attack. Bezout's identity gives a and b with a e1 + b e2 = 1, so
+/// c1^a c2^b = m^(a e1 + b e2) = m mod n. Returns `None` if the moduli differ, the exponents share a
+/// factor, or a cipher has no inverse mod `n`.
+///
+/// # Arguments
+/// * `key_one` - First public key
+/// * `key_two` - Second public key, with the same modulus as `key_one`
+/// * `cipher_one` - The block encrypted with `key_one`
+/// * `cipher_two` - The block encrypted with `key_two`
+pub fn common_modulus_block(key_one: &RsaPublicKey, key_two: &RsaPublicKey, cipher_one: &BigUint,
+                            cipher_two: &BigUint) -> Option<BigUint> {
+    if key_one.n != key_two.n {
+        return None;
+    }
+    let e_one = BigInt::from_biguint(Sign::Plus, key_one.e.clone());
+    let e_two = BigInt::from_biguint(Sign::Plus, key_two.e.clone());
+    let bezout = e_one.extended_gcd(&e_two);
+    if !bezout.gcd.is_one() {
+        return None;
+    }
+
+    let n = &key_one.n;
+    let part_one = _signed_pow_mod(cipher_one, &bezout.x, n)?;
+    let part_two = _signed_pow_mod(cipher_two, &bezout.y, n)?;
+    Some(part_one * part_two % n)
+}
+
+/// Recovers a string encrypted with `rsa::encrypt_str` under two public keys with the same modulus,
+/// applying `common_modulus_block` to each block
+///
+/// # Arguments
+/// * `key_one` - First public key
+/// * `key_two` - Second public key, with the same modulus as `key_one`
+/// * `cipher_one` - The string encrypted with `key_one`
+/// * `cipher_two` - The string encrypted with `key_two`
+pub fn common_modulus(key_one: &RsaPublicKey, key_two: &RsaPublicKey, cipher_one: &[BigUint],
+                      cipher_two: &[BigUint]) -> Option<String> {
+    if cipher_one.len() != cipher_two.len() {
+        return None;
+    }
+
+    let packed = cipher_one.iter().zip(cipher_two)
+        .map(|(one, two)| common_modulus_block(key_one, key_two, one, two))
+        .collect::<Option<Vec<BigUint>>>()?;
+    let msg = rsa::_unpack_bytes(&packed, key_one.size().checked_sub(1)?)?;
+    String::from_utf8(msg).ok()
+}
+
+/// Returns, for each of `moduli`, its gcd with the product of all the others, by Bernstein's batch
+/// GCD. A product tree multiplies the moduli together in pairs, then a remainder tree reduces the
+/// product mod the square of each modulus on the way back down; for modulus n with remainder r,
+/// gcd(r / n, n) is the gcd with the rest. This takes quasi-linear time rather than the quadratic
+/// time of trying every pair.
+///
+/// # Arguments
+/// * `moduli` - Moduli to compare, each greater than 1
+pub fn batch_gcd(moduli: &[BigUint]) -> Vec<BigUint> {
+    if moduli.is_empty() {
+        return Vec::new();
+    }
+
+    let mut tree = vec![moduli.to_vec()];
+    while tree.last().unwrap().len() > 1 {
+        let level = tree.last().unwrap().chunks(2).map(|pair| pair.iter().product()).collect();
+        tree.push(level);
+    }
+
+    let mut remainders = tree.pop().unwrap();
+    while let Some(level) = tree.pop() {
+        remainders = level.iter().enumerate()
+            .map(|(i, node)| &remainders[i / 2] % (node * node))
+            .collect();
+    }
+
+    moduli.iter().zip(remainders)
+        .map(|(n, remainder)| (remainder / n).gcd(n))
+        .collect()
+}
+
+/// Factors every key among `keys` that shares a prime with another, using `batch_gcd`, and returns
+/// the private key of each one factored. Keys made by a generator with too little entropy often
+/// share primes. Keys whose primes are both shared, with different keys, are split by comparing
+/// them with each other key in turn. Identical moduli can't be split this way.
+///
+/// # Arguments
+/// * `keys` - Public keys to compare, e.g. read with `io::read_public_key_from_file`
+pub fn shared_factors(keys: &[RsaPublicKey]) -> Vec<Option<RsaPrivateKey>> {
+    let moduli: Vec<BigUint> = keys.iter().map(|key| key.n.clone()).collect();
+    let gcds = batch_gcd(&moduli);
+
+    keys.iter().zip(gcds)
+        .map(|(key, g)| {
+            let p = if g == key.n {
+                moduli.iter()
+                    .map(|other| other.gcd(&key.n))
+                    .find(|g| !g.is_one() && *g != key.n)?
+            } else if g.is_one() {
+                return None;
+            } else {
+                g
+            };
+            let q = &key.n / &p;
+            _private_key(key, p, q)
+        })
+        .collect()
+}
@@ -1,59 +1,325 @@
 use std::io;
+use std::time::{Duration, Instant};
 use num::BigUint;
 
-use crate::{rsa, io as asdf};
-use std::error::Error;
+use rsa_vis::{rsa, rand, signature, primes, factor, vis, io as asdf};
+use rsa_vis::factor::Progress;
+use rsa_vis::siqs::SieveProgress;
+use rsa_vis::rsa::{RsaPrivateKey, RsaPublicKey, KeyBuilder, PrimeMethod, Blinding};
+use rsa_vis::hash::HashAlgorithm;
+use rsa_vis::primes::PrimeCertificate;
+
+/// How often `fk` prints sieve progress
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Padding scheme applied by the encryption and decryption commands
+#[derive(Clone, Copy, PartialEq)]
+enum Padding {
+    /// Textbook RSA on 4-byte blocks
+    None,
+    /// RSAES-PKCS1-v1_5
+    Pkcs1v15,
+    /// RSAES-OAEP with the given hash and no label
+    Oaep(HashAlgorithm),
+}
+
+/// Signature scheme used by the signing and verification commands
+#[derive(Clone, Copy, PartialEq)]
+enum SignatureScheme {
+    /// RSASSA-PKCS1-v1_5 with the given hash
+    Pkcs1v15(HashAlgorithm),
+    /// RSASSA-PSS with the given hash, and a salt as long as the digest
+    Pss(HashAlgorithm),
+}
 
 fn print_help() {
     println!("Key generation: ");
-    println!("\t k -> Generate new key and store in memory");
+    println!("\t k [bits] [exponent] -> Generate new key and store in memory. Defaults to 1024 bits, exponent 65537");
+    println!("\t pm <random|fips|provable|safe|strong> -> Set how primes are chosen for new keys. Default is random");
+    println!("\t fp [bits] -> Generate a FIPS 186-5 prime pair for a modulus of [bits] bits and show how many candidates were tried");
     println!("\t wk -> Write key stored in memory to file");
     println!("\t rk -> Read key from disk. Must be present in current directory.");
+    println!("\t uk -> Upgrade a key saved without its primes: recover them for the stored key, or the key on disk if none is stored, and write the full key to disk");
+    println!("\t wpc <filename> -> Write certificates for the primes of a key generated with `pm provable` to <filename>");
+    println!("\t vpc <filename> -> Read prime certificates from <filename> and check them");
 
     println!("Encryption: ");
     println!("\t e <message> -> Encrypt message using stored key, storing cipher in memory.");
     println!("\t wc <filename> -> Write stored cipher to <filename>");
+    println!("\t p <none|pkcs1|oaep> [sha1|sha256|sha384|sha512] -> Set padding used for encryption and decryption. Default is none");
     println!("\t pc -> Prints stored cipher to stdout. Warning: very long line");
+    println!("\t fe <source> <dest> -> Encrypt a file of any size using stored key, or pub_key.txt if no key is stored. `-` reads stdin or writes stdout");
+    println!("\t fm <source> <dest> <pub_key file>... -> Encrypt a file for several recipients, any of whom can decrypt it with `fd`");
 
     println!("Decryption: ");
     println!("\t d -> Decrypt cipher stored in memory, display result to stdout");
+    println!("\t bl <off|base|exponent> -> Set blinding for decryption and signing. Default is base; `off` shows plain textbook RSA");
     println!("\t df <filename> -> Read cipher from file and decrypt using stored key, display result to stdout");
+    println!("\t fd <source> <dest> -> Decrypt a file written by `fe` or `fm` using stored key. `-` reads stdin or writes stdout");
+
+    println!("Signatures: ");
+    println!("\t ss <pkcs1|pss> [sha256|sha384|sha512] -> Set signature scheme. Default is pkcs1 sha256");
+    println!("\t sg <message> -> Sign message using stored key, storing signature in memory");
+    println!("\t vf <message> -> Verify stored signature against message using stored key");
+    println!("\t ws <filename> -> Write stored signature to <filename>");
+    println!("\t rs <filename> -> Read signature from <filename> into memory");
+
+    println!("Factoring: ");
+    println!("\t fk [seconds] [chart.bmp] -> Try to factor the modulus of the stored key, or of pub_key.txt if no key is stored, for up to [seconds] seconds. Default is 10. If the quadratic sieve is used, a chart of relations collected is written to [chart.bmp]");
 
     println!("Misc: ");
     println!("\t q -> Quit.");
     println!("\t s -> Print status. Shows whether key/cipher is stored in memory");
     println!("\t h -> Print this help menu again");
+    println!("`fe`, `fm` and `fd` can also be given as arguments, e.g. `tar c dir | rsa_vis fe - backup.rsv`, to run once");
+    println!("with the key files in the current directory and without this prompt.");
+}
+
+/// Helper function, reads the public key in each of `paths`
+fn read_public_keys(paths: &[&str]) -> io::Result<Vec<RsaPublicKey>> {
+    paths.iter().map(|path| asdf::read_public_key_from_file(path)).collect()
+}
+
+/// Runs the command given as program arguments, `fe`, `fm` or `fd` with a source and destination,
+/// using the key files in the current directory or, for `fm`, those given. Messages go to stderr,
+/// since stdout may be the destination. Returns the exit code.
+pub fn run_command(args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["fe", src, dest] => asdf::read_public_key_from_disk().and_then(|key| asdf::encrypt_file(src, dest, &key)),
+        ["fm", src, dest, paths @ ..] if !paths.is_empty() => read_public_keys(paths)
+            .and_then(|keys| asdf::encrypt_file_for_recipients(src, dest, &keys)),
+        ["fd", src, dest] => asdf::read_key_from_disk().and_then(|key| asdf::decrypt_file(src, dest, &key)),
+        _ => {
+            eprintln!("Usage: `rsa_vis <fe|fd> <source|-> <dest|->`, `rsa_vis fm <source|-> <dest|-> <pub_key file>...`,");
+            eprintln!("or no arguments for the prompt");
+            return 2;
+        },
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {eprintln!("Error: {}", e); 1},
+    }
 }
 
 
+/// Decrypts `cipher` with `key` using the given padding scheme, and prints the result
+fn print_decryption(cipher: &[BigUint], key: &RsaPrivateKey, padding: Padding) {
+    match padding {
+        Padding::None => match rsa::decrypt_str(cipher, key) {
+            Ok(msg) => println!("> Decryption result: {}", msg),
+            Err(e) => println!("> Error: {}", e),
+        },
+        Padding::Pkcs1v15 => match rsa::decrypt_str_pkcs1v15(cipher, key) {
+            Ok(msg) => println!("> Decryption result: {}", msg),
+            Err(e) => println!("> Error: {}", e),
+        },
+        Padding::Oaep(hash) => match rsa::decrypt_str_oaep(cipher, key, hash) {
+            Ok(msg) => println!("> Decryption result: {}", msg),
+            Err(e) => println!("> Error: {}", e),
+        },
+    }
+}
+
+/// Parses the name of a hash function given to the CLI, defaulting to SHA-256 when none is given
+fn parse_hash(name: Option<&&str>) -> Option<HashAlgorithm> {
+    match name {
+        None | Some(&"sha256") => Some(HashAlgorithm::Sha256),
+        Some(&"sha1") => Some(HashAlgorithm::Sha1),
+        Some(&"sha384") => Some(HashAlgorithm::Sha384),
+        Some(&"sha512") => Some(HashAlgorithm::Sha512),
+        _ => None,
+    }
+}
+
 pub fn init_cli_interface() {
-    println!("Rust implementation of RSA-1024, written by Ariel Young and Nashir Janmohamed\n");
+    println!("Rust implementation of RSA, written by Ariel Young and Nashir Janmohamed\n");
     println!("Commands are as follows -- ");
     print_help();
 
-    let mut stored_key: Option<((BigUint, BigUint), BigUint)> = None;
+    let mut stored_key: Option<RsaPrivateKey> = None;
     let mut stored_cipher: Option<Vec<BigUint>> = None;
+    let mut stored_signature: Option<Vec<u8>> = None;
+    let mut padding = Padding::None;
+    let mut sig_scheme = SignatureScheme::Pkcs1v15(HashAlgorithm::Sha256);
+    let mut prime_method = PrimeMethod::Random;
+    let mut blinding = Blinding::default();
+    let mut stored_certificates: Vec<PrimeCertificate> = Vec::new();
+    let mut rng = rand::new_secure();
 
 
-    'outer: loop {
+    loop {
         println!();
         let mut input = String::new();
         io::stdin().read_line(&mut input).expect("> Unable to read input");
-        let mut parts: Vec<&str> = input.split_ascii_whitespace().collect();
+        let parts: Vec<&str> = input.split_ascii_whitespace().collect();
+        if parts.is_empty() {
+            continue;
+        }
 
         match parts[0] {
             "k" => {
+                let mut builder = KeyBuilder::new().prime_method(prime_method);
+                if let Some(bits) = parts.get(1) {
+                    match bits.parse() {
+                        Ok(bits) => builder = builder.bits(bits),
+                        Err(_) => {println!("> Usage: `k [bits] [exponent]`"); continue;},
+                    }
+                }
+                if let Some(exponent) = parts.get(2) {
+                    match exponent.parse() {
+                        Ok(exponent) => builder = builder.exponent(exponent),
+                        Err(_) => {println!("> Usage: `k [bits] [exponent]`"); continue;},
+                    }
+                }
+
                 println!("> Generating key...");
-                stored_key = Some(rsa::gen_key());
-                println!("> Finished!");
+                match builder.build_with_certificates(&mut rng) {
+                    Ok((key, certificates)) => {
+                        println!("> Finished! Modulus is {} bits", key.n.bits());
+                        if !certificates.is_empty() {
+                            println!("> Primes proven, write their certificates with `wpc <filename>`");
+                        }
+                        stored_key = Some(key);
+                        stored_certificates = certificates;
+                    },
+                    Err(e) => println!("> Error: {}", e),
+                }
+            },
+            "pm" => match parts.get(1) {
+                Some(&"random") => {prime_method = PrimeMethod::Random; println!("> Prime method: random");},
+                Some(&"fips") => {prime_method = PrimeMethod::Fips186; println!("> Prime method: FIPS 186-5");},
+                Some(&"provable") => {prime_method = PrimeMethod::Provable; println!("> Prime method: provable");},
+                Some(&"safe") => {prime_method = PrimeMethod::Safe; println!("> Prime method: safe primes");},
+                Some(&"strong") => {prime_method = PrimeMethod::Strong; println!("> Prime method: strong primes");},
+                _ => println!("> Usage: `pm <random|fips|provable|safe|strong>`"),
+            },
+            "wpc" => match parts.get(1) {
+                None => println!("> Usage: `wpc <filename>`"),
+                Some(_) if stored_certificates.is_empty() => println!("> Error: No stored certificates"),
+                Some(path) => match asdf::write_certificates_to_disk(&stored_certificates, path) {
+                    Ok(()) => println!("> Done!"),
+                    Err(e) => println!("> Error writing certificates: {}", e),
+                },
+            },
+            "vpc" => match parts.get(1).map(|path| asdf::read_certificates_from_disk(path)) {
+                None => println!("> Usage: `vpc <filename>`"),
+                Some(Err(e)) => println!("> Error reading certificates: {}", e),
+                Some(Ok(certificates)) => for certificate in &certificates {
+                    let prime = certificate.prime();
+                    let status = if primes::verify_certificate(certificate) { "valid" } else { "INVALID" };
+                    let of_key = match &stored_key {
+                        Some(key) if key.p.as_ref() == Some(&prime) => " (p of stored key)",
+                        Some(key) if key.q.as_ref() == Some(&prime) => " (q of stored key)",
+                        _ => "",
+                    };
+                    println!("> Certificate for {}-bit prime{}: {}", prime.bits(), of_key, status);
+                },
+            },
+            "fp" => {
+                let bits = match parts.get(1).map(|b| b.parse()) {
+                    None => 1024,
+                    Some(Ok(bits)) => bits,
+                    Some(Err(_)) => {println!("> Usage: `fp [bits]`"); continue;},
+                };
+                let exponent = BigUint::from(65_537_u32);
+                match primes::gen_fips_rsa_primes(bits, &exponent, &mut rng) {
+                    Some(pair) => {
+                        println!("> p = {} ({} candidates tried)", pair.p, pair.p_candidates);
+                        println!("> q = {} ({} candidates tried)", pair.q, pair.q_candidates);
+                    },
+                    None => println!("> Error: no primes found, or [bits] is odd or too small"),
+                }
             },
-            "wk" => match stored_key.clone() {
+            "fk" => {
+                let seconds = match parts.get(1).map(|s| s.parse()) {
+                    None => 10,
+                    Some(Ok(seconds)) => seconds,
+                    Some(Err(_)) => {println!("> Usage: `fk [seconds] [chart.bmp]`"); continue;},
+                };
+                let n = match &stored_key {
+                    Some(key) => key.n.clone(),
+                    None => match asdf::read_public_key_from_disk() {
+                        Ok(key) => key.n,
+                        Err(e) => {println!("> Error: No stored key, and unable to read public key: {}", e); continue;},
+                    },
+                };
+
+                println!("> Factoring {}-bit modulus...", n.bits());
+                let start = Instant::now();
+                let mut history: Vec<SieveProgress> = Vec::new();
+                let mut last_b1 = 0;
+                let mut last_report = Instant::now();
+                let result = factor::factorise_with_progress(&n, Duration::from_secs(seconds), &mut rng, |progress| {
+                    match progress {
+                        Progress::Curve { b1, .. } if b1 != last_b1 => {
+                            println!("> Trying elliptic curves with B1 = {}", b1);
+                            last_b1 = b1;
+                        },
+                        Progress::Curve { .. } => {},
+                        Progress::Sieve(report) => {
+                            // A new sieve run for another composite starts a new chart
+                            if history.last().is_some_and(|last| report.elapsed < last.elapsed) {
+                                history.clear();
+                            }
+                            history.push(report);
+                            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                                println!("> Sieving: {}/{} relations ({} full, {} from {} partial) after {} polynomials",
+                                         report.relations(), report.target, report.full_relations,
+                                         report.combined_relations, report.partial_relations, report.polynomials);
+                                last_report = Instant::now();
+                            }
+                        },
+                    }
+                });
+                for f in &result.factors {
+                    println!("> Factor {} ({:?})", f.prime, f.method);
+                }
+                for part in &result.unfactored {
+                    println!("> Unable to split {}-bit composite {}", part.bits(), part);
+                }
+                println!("> {} after {:.2?}", if result.is_complete() { "Done" } else { "Gave up" }, start.elapsed());
+
+                match (parts.get(2), history.is_empty()) {
+                    (None, _) => {},
+                    (Some(_), true) => println!("> The quadratic sieve wasn't used, so there is no chart"),
+                    (Some(path), false) => match vis::relations_chart(&history, 640, 480).save(path) {
+                        Ok(()) => println!("> Wrote chart of relations to {}", path),
+                        Err(e) => println!("> Error writing chart: {}", e),
+                    },
+                }
+            },
+            "wk" => match &stored_key {
                 Some(key) => {asdf::write_json_to_disk(key); println!("> Done!");},
                 None => println!("> Error: No stored key")
             },
             "rk" => match asdf::read_key_from_disk() {
                 Ok(key) => {stored_key = Some(key); println!("> Done!");},
-                Err(e) => println!("> Error reading key from file: {}", e.to_string()),
+                Err(e) => println!("> Error reading key from file: {}", e),
+            },
+            "uk" => {
+                let key = match stored_key.take().map_or_else(asdf::read_key_from_disk, Ok) {
+                    Ok(key) => key,
+                    Err(e) => {println!("> Error reading key from file: {}", e); continue;},
+                };
+                if key.p.is_some() && key.q.is_some() {
+                    println!("> Key already has its primes");
+                    stored_key = Some(key);
+                    continue;
+                }
+
+                println!("> Recovering primes...");
+                match key.with_primes(&mut rng) {
+                    Ok(full_key) => {
+                        asdf::write_json_to_disk(&full_key);
+                        println!("> Done! Wrote key with primes and CRT values to disk");
+                        stored_key = Some(full_key);
+                    },
+                    Err(e) => {
+                        println!("> Error: {}", e);
+                        stored_key = Some(key);
+                    },
+                }
             },
 
             "e" => {
@@ -63,10 +329,20 @@ pub fn init_cli_interface() {
                 } else if parts.len() >= 2 {
                     println!("> Encrypting message...");
                     let key = stored_key.clone().unwrap();
-                    let res = rsa::encrypt_str(&parts[1..].join(" "), key.0);
-                    println!("> Finished!");
-                    println!("> Result: {:?}", res);
-                    stored_cipher = Some(res);
+                    let msg = parts[1..].join(" ");
+                    let res = match padding {
+                        Padding::None => rsa::encrypt_str(&msg, &key.to_public_key()),
+                        Padding::Pkcs1v15 => rsa::encrypt_str_pkcs1v15(&msg, &key.to_public_key(), &mut rng),
+                        Padding::Oaep(hash) => rsa::encrypt_str_oaep(&msg, &key.to_public_key(), hash, &mut rng),
+                    };
+                    match res {
+                        Ok(res) => {
+                            println!("> Finished!");
+                            println!("> Result: {:?}", res);
+                            stored_cipher = Some(res);
+                        },
+                        Err(e) => println!("> Error: {}", e),
+                    }
                 } else {
                     println!("> Error: invalid parameters to 'e'");
                 }
@@ -84,6 +360,16 @@ pub fn init_cli_interface() {
                 }
             },
 
+            "p" => match parts.get(1) {
+                Some(&"none") => {padding = Padding::None; println!("> Padding: none");},
+                Some(&"pkcs1") => {padding = Padding::Pkcs1v15; println!("> Padding: PKCS#1 v1.5");},
+                Some(&"oaep") => match parse_hash(parts.get(2)) {
+                    Some(hash) => {padding = Padding::Oaep(hash); println!("> Padding: OAEP ({:?})", hash);},
+                    None => println!("> Error: unknown hash, expected one of sha1, sha256, sha384, sha512"),
+                },
+                _ => println!("> Usage: `p <none|pkcs1|oaep> [sha1|sha256|sha384|sha512]`"),
+            },
+
             "pc" => match stored_cipher.clone() {
                 Some(t) => {
                     println!("> Stored cipher: {:?}", t);
@@ -102,14 +388,20 @@ pub fn init_cli_interface() {
                     println!("> Error: No stored key");
                     println!("> You probably want to read one from disk using 'rk'");
                 } else {
-                    let key = stored_key.clone().unwrap();
-                    println!("> Decryption result: {}", rsa::decrypt_str(&stored_cipher.clone().unwrap(),
-                                                                       key.1, (key.0).0));
+                    let key = stored_key.clone().unwrap().with_blinding(blinding);
+                    print_decryption(&stored_cipher.clone().unwrap(), &key, padding);
                 }
 
 
             },
 
+            "bl" => match parts.get(1) {
+                Some(&"off") => {blinding = Blinding::None; println!("> Blinding: off");},
+                Some(&"base") => {blinding = Blinding::Base; println!("> Blinding: base");},
+                Some(&"exponent") => {blinding = Blinding::BaseAndExponent; println!("> Blinding: base and exponent");},
+                _ => println!("> Usage: `bl <off|base|exponent>`"),
+            },
+
             "df" => {
                 if parts.len() != 2 {
                     println!("> Usage: `df <filename>");
@@ -121,15 +413,138 @@ pub fn init_cli_interface() {
                     match asdf::read_cipher_from_disk(parts[1]) {
                         Ok(c) => {
                             println!("Done!");
-                            let key = stored_key.clone().unwrap();
-                            println!("> Decryption result: {}", rsa::decrypt_str(&c, key.1, (key.0).0));
+                            let key = stored_key.clone().unwrap().with_blinding(blinding);
+                            print_decryption(&c, &key, padding);
+                        },
+
+                        Err(_) => println!("failed \n> Error reading cipher from disk"),
+                    }
+                }
+            },
+
+            "fe" => {
+                if parts.len() != 3 {
+                    println!("> Usage: `fe <source|-> <dest|->`");
+                    continue;
+                }
+                let key = match &stored_key {
+                    Some(key) => key.to_public_key(),
+                    None => match asdf::read_public_key_from_disk() {
+                        Ok(key) => key,
+                        Err(e) => {println!("> Error: No stored key, and unable to read public key: {}", e); continue;},
+                    },
+                };
+                match asdf::encrypt_file(parts[1], parts[2], &key) {
+                    Ok(()) => println!("> Done!"),
+                    Err(e) => println!("> Error encrypting file: {}", e),
+                }
+            },
+
+            "fm" => {
+                if parts.len() < 4 {
+                    println!("> Usage: `fm <source|-> <dest|-> <pub_key file>...`");
+                    continue;
+                }
+                match read_public_keys(&parts[3..]) {
+                    Ok(keys) => match asdf::encrypt_file_for_recipients(parts[1], parts[2], &keys) {
+                        Ok(()) => println!("> Encrypted for {} recipients", keys.len()),
+                        Err(e) => println!("> Error encrypting file: {}", e),
+                    },
+                    Err(e) => println!("> Error reading public key: {}", e),
+                }
+            },
+
+            "fd" => match &stored_key {
+                _ if parts.len() != 3 => println!("> Usage: `fd <source|-> <dest|->`"),
+                None => println!("> Error: No stored key\n> You probably want to read one from disk using 'rk'"),
+                Some(key) => match asdf::decrypt_file(parts[1], parts[2], &key.clone().with_blinding(blinding)) {
+                    Ok(()) => println!("> Done!"),
+                    Err(e) => println!("> Error decrypting file: {}", e),
+                },
+            },
+
+            "ss" => match (parts.get(1), parse_hash(parts.get(2))) {
+                (Some(&"pkcs1"), Some(HashAlgorithm::Sha1)) | (Some(&"pss"), Some(HashAlgorithm::Sha1)) =>
+                    println!("> Error: SHA-1 is too weak for signatures, use sha256, sha384 or sha512"),
+                (Some(&"pkcs1"), Some(hash)) => {
+                    sig_scheme = SignatureScheme::Pkcs1v15(hash);
+                    println!("> Signature scheme: PKCS#1 v1.5 ({:?})", hash);
+                },
+                (Some(&"pss"), Some(hash)) => {
+                    sig_scheme = SignatureScheme::Pss(hash);
+                    println!("> Signature scheme: PSS ({:?})", hash);
+                },
+                _ => println!("> Usage: `ss <pkcs1|pss> [sha256|sha384|sha512]`"),
+            },
+
+            "sg" => {
+                if stored_key.is_none() {
+                    println!("> Error: No stored key");
+                    println!("> Either generate one, or read from file using 'rk'");
+                } else if parts.len() >= 2 {
+                    let key = stored_key.clone().unwrap().with_blinding(blinding);
+                    let msg = parts[1..].join(" ");
+                    let res = match sig_scheme {
+                        SignatureScheme::Pkcs1v15(hash) => signature::sign_pkcs1v15(msg.as_bytes(), &key, hash),
+                        SignatureScheme::Pss(hash) =>
+                            signature::sign_pss(msg.as_bytes(), &key, hash, hash.output_len(), &mut rng),
+                    };
+                    match res {
+                        Ok(sig) => {
+                            print!("> Signature: ");
+                            for b in &sig { print!("{:02x}", b); }
+                            println!();
+                            stored_signature = Some(sig);
                         },
+                        Err(e) => println!("> Error: {}", e),
+                    }
+                } else {
+                    println!("> Usage: `sg <message>`");
+                }
+            },
 
-                        Err(e) => println!("failed \n> Error reading cipher from disk"),
+            "vf" => {
+                if stored_signature.is_none() {
+                    println!("> Error: No stored signature.");
+                    println!("> Either sign a message using 'sg', or read a signature from file using 'rs'");
+                } else if stored_key.is_none() {
+                    println!("> Error: No stored key");
+                    println!("> You probably want to read one from disk using 'rk'");
+                } else if parts.len() >= 2 {
+                    let key = stored_key.clone().unwrap().to_public_key();
+                    let sig = stored_signature.clone().unwrap();
+                    let msg = parts[1..].join(" ");
+                    let res = match sig_scheme {
+                        SignatureScheme::Pkcs1v15(hash) => signature::verify_pkcs1v15(msg.as_bytes(), &sig, &key, hash),
+                        SignatureScheme::Pss(hash) =>
+                            signature::verify_pss(msg.as_bytes(), &sig, &key, hash, hash.output_len()),
+                    };
+                    match res {
+                        Ok(()) => println!("> Signature is valid"),
+                        Err(e) => println!("> Error: {}", e),
                     }
+                } else {
+                    println!("> Usage: `vf <message>`");
                 }
             },
 
+            "ws" => match (&stored_signature, parts.get(1)) {
+                (None, _) => println!("> Error: No signature stored in memory. Please sign something."),
+                (Some(sig), Some(path)) => match asdf::write_signature_to_disk(sig, path) {
+                    Ok(()) => println!("> Done!"),
+                    Err(e) => println!("> Error writing signature to file: {}", e),
+                },
+                (Some(_), None) => println!("> Usage: `ws <filename>`"),
+            },
+
+            "rs" => match parts.get(1) {
+                Some(path) => match asdf::read_signature_from_disk(path) {
+                    Ok(sig) => {stored_signature = Some(sig); println!("> Done!");},
+                    Err(e) => println!("> Error reading signature from file: {}", e),
+                },
+                None => println!("> Usage: `rs <filename>`"),
+            },
+
             "s" => {
                 if stored_key.is_none() {
                     println!("> Key stored in memory: no");
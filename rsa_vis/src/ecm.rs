@@ -0,0 +1,209 @@
+use num::BigUint;
+use num::Integer;
+use num::traits::One;
+
+use crate::primes;
+use crate::rand::RandomSource;
+use crate::rsa;
+
+/// Stage 2 steps through multiples of this, pairing each prime with its distance to the nearest one.
+/// Used once the stage 1 bound is big enough that the table of distances is worth building.
+const STAGE_TWO_STEP: u64 = 2310;
+
+/// Stage 2 step for small stage 1 bounds
+const SMALL_STAGE_TWO_STEP: u64 = 210;
+
+/// A point on a Montgomery curve in projective x-only coordinates, X/Z. The y coordinate is never
+/// needed, which halves the work of each step.
+#[derive(Clone, Debug)]
+struct Point {
+    x: BigUint,
+    z: BigUint,
+}
+
+/// A Montgomery curve By^2 = x^3 + Ax^2 + x modulo `n`, stored as a24 = (A + 2) / 4, the only
+/// constant the x-only formulas use
+struct Curve<'a> {
+    n: &'a BigUint,
+    a24: BigUint,
+}
+
+impl Curve<'_> {
+    fn _mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a * b % self.n
+    }
+
+    fn _add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let sum = a + b;
+        if sum >= *self.n { sum - self.n } else { sum }
+    }
+
+    fn _sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        if a >= b { a - b } else { a + self.n - b }
+    }
+
+    /// Returns 2P
+    fn double(&self, p: &Point) -> Point {
+        let sum = self._add(&p.x, &p.z);
+        let diff = self._sub(&p.x, &p.z);
+        let sum_squared = self._mul(&sum, &sum);
+        let diff_squared = self._mul(&diff, &diff);
+        // 4XZ = (X + Z)^2 - (X - Z)^2
+        let four_xz = self._sub(&sum_squared, &diff_squared);
+
+        Point {
+            x: self._mul(&sum_squared, &diff_squared),
+            z: self._mul(&four_xz, &self._add(&diff_squared, &self._mul(&self.a24, &four_xz))),
+        }
+    }
+
+    /// Returns P + Q, given P - Q. Without y coordinates the sum can only be found from the difference.
+    fn add(&self, p: &Point, q: &Point, difference: &Point) -> Point {
+        let u = self._mul(&self._sub(&p.x, &p.z), &self._add(&q.x, &q.z));
+        let v = self._mul(&self._add(&p.x, &p.z), &self._sub(&q.x, &q.z));
+        let sum = self._add(&u, &v);
+        let diff = self._sub(&u, &v);
+
+        Point {
+            x: self._mul(&difference.z, &self._mul(&sum, &sum)),
+            z: self._mul(&difference.x, &self._mul(&diff, &diff)),
+        }
+    }
+
+    /// Returns kP with the Montgomery ladder, which keeps R1 - R0 = P throughout so every addition
+    /// knows its difference
+    fn multiply(&self, p: &Point, k: u64) -> Point {
+        if k == 1 {
+            return p.clone();
+        }
+
+        let mut r0 = p.clone();
+        let mut r1 = self.double(p);
+        for bit in (0..63 - k.leading_zeros()).rev() {
+            if (k >> bit) & 1 == 1 {
+                r0 = self.add(&r1, &r0, p);
+                r1 = self.double(&r1);
+            } else {
+                r1 = self.add(&r0, &r1, p);
+                r0 = self.double(&r0);
+            }
+        }
+        r0
+    }
+}
+
+/// Helper function, returns a curve and point on it from Suyama's parametrisation with parameter
+/// `sigma`, which makes the group order divisible by 12. Returns the factor instead if setting the
+/// curve up needs an inverse that doesn't exist modulo `n`.
+fn _suyama_curve<'a>(n: &'a BigUint, sigma: &BigUint) -> Result<(Curve<'a>, Point), Option<BigUint>> {
+    let u = (sigma * sigma + n - BigUint::from(5_u32)) % n;
+    let v = (sigma << 2) % n;
+    let u_cubed = &u * &u * &u % n;
+    let v_minus_u = (&v + n - &u) % n;
+
+    // a24 = (v - u)^3 (3u + v) / (16 u^3 v)
+    let numerator = v_minus_u.modpow(&BigUint::from(3_u32), n) * ((&u * 3_u32 + &v) % n) % n;
+    let denominator = ((&u_cubed * &v) << 4) % n;
+    let g = denominator.gcd(n);
+    if !g.is_one() {
+        return Err(if g < *n { Some(g) } else { None });
+    }
+    let a24 = numerator * rsa::_modular_multiplicative_inverse(denominator, n.clone()) % n;
+
+    let point = Point { x: u_cubed, z: v.modpow(&BigUint::from(3_u32), n) };
+    Ok((Curve { n, a24 }, point))
+}
+
+/// Helper function, stage 2 of ECM by the standard continuation. Looks for a single prime q in
+/// (`b1`, `b2`] with qP = O modulo a factor, by writing q = mD ± j and comparing x(mDP) with x(jP), both
+/// of which are stepped to cheaply. Returns the product of the cross-differences, which shares the
+/// factor with `n` if there is one.
+fn _stage_two(curve: &Curve, p: &Point, b1: u64, b2: u64) -> BigUint {
+    let step = if b1 < STAGE_TWO_STEP { SMALL_STAGE_TWO_STEP } else { STAGE_TWO_STEP };
+
+    // Baby steps: jP for odd j up to step / 2
+    let p2 = curve.double(p);
+    let mut baby = vec![p.clone(), curve.add(&p2, p, p)];
+    while baby.len() < (step / 4 + 1) as usize {
+        let j = baby.len();
+        baby.push(curve.add(&baby[j - 1], &p2, &baby[j - 2]));
+    }
+
+    // Giant steps: mDP for m = m0, m0 + 1, ..., where m0 D is nearest to the first prime above b1
+    let giant = curve.multiply(p, step);
+    let mut m = ((b1 + step / 2) / step).max(1);
+    let mut current = curve.multiply(p, m * step);
+    let mut next = curve.multiply(p, (m + 1) * step);
+
+    let mut product: BigUint = One::one();
+    for q in primes::primes_in_range(b1 + 1, b2 + 1) {
+        while q > m * step + step / 2 {
+            let after = curve.add(&next, &giant, &current);
+            current = next;
+            next = after;
+            m += 1;
+        }
+
+        let j = (q as i64 - (m * step) as i64).unsigned_abs();
+        let baby_point = &baby[(j / 2) as usize];
+        let cross = curve._sub(&curve._mul(&current.x, &baby_point.z), &curve._mul(&baby_point.x, &current.z));
+        product = curve._mul(&product, &cross);
+    }
+    product
+}
+
+/// Helper function, tries one curve with Suyama parameter `sigma`
+fn _ecm_curve(n: &BigUint, sigma: &BigUint, b1: u64, b2: u64) -> Option<BigUint> {
+    let (curve, mut point) = match _suyama_curve(n, sigma) {
+        Ok(setup) => setup,
+        Err(factor) => return factor,
+    };
+
+    // Stage 1: multiply by the largest power of each prime up to b1
+    for p in primes::primes_in_range(2, b1 + 1) {
+        let mut power = p;
+        while let Some(next) = power.checked_mul(p).filter(|next| *next <= b1) {
+            power = next;
+        }
+        point = curve.multiply(&point, power);
+    }
+    let g = point.z.gcd(n);
+    if !g.is_one() {
+        return if g < *n { Some(g) } else { None };
+    }
+
+    if b2 > b1 && b1 >= SMALL_STAGE_TWO_STEP {
+        let g = _stage_two(&curve, &point, b1, b2).gcd(n);
+        if !g.is_one() && g < *n {
+            return Some(g);
+        }
+    }
+    None
+}
+
+/// Returns a factor of `n` found by Lenstra's elliptic curve method on Montgomery curves, trying up
+/// to `curves` random curves. Each curve finds a factor p when its group order modulo p has every
+/// prime factor at most `b1`, bar one at most `b2`, so unlike Pollard's p - 1 a failure can be retried
+/// with a fresh curve. ECM finds small factors of big numbers quickly; its running time depends on
+/// the size of the factor found, not of `n`.
+///
+/// # Arguments
+/// * `n` - Odd number to factor, with no factors below 7
+/// * `b1` - Stage 1 bound
+/// * `b2` - Stage 2 bound. Usually about 100 times `b1`; stage 2 is skipped if this isn't above `b1`.
+/// * `curves` - Number of curves to try
+/// * `rng` - Random number generator for choosing curves
+pub fn ecm<R: RandomSource>(n: &BigUint, b1: u64, b2: u64, curves: usize, rng: &mut R) -> Option<BigUint> {
+    if n.is_even() || *n <= BigUint::from(5_u32) {
+        return None;
+    }
+
+    for _ in 0..curves {
+        let sigma = rng.gen_bigint_range(&BigUint::from(6_u32), n);
+        if let Some(factor) = _ecm_curve(n, &sigma, b1, b2) {
+            return Some(factor);
+        }
+    }
+    None
+}
+
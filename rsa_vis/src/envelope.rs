@@ -0,0 +1,223 @@
+use std::io;
+use std::io::{Read, Write};
+
+use crate::aead;
+use crate::hash::HashAlgorithm;
+use crate::hybrid;
+use crate::hybrid::KeyWrap;
+use crate::rand::RandomSource;
+use crate::rsa;
+use crate::rsa::{Error, RsaPrivateKey, RsaPublicKey};
+use crate::stream;
+use crate::stream::{Decryptor, Encryptor};
+
+/// Length of a recipient key ID in bytes
+pub const KEY_ID_SIZE: usize = 16;
+
+/// Length of the fixed part of an envelope header: magic, version, key wrap, hash and number of
+/// recipients
+const FIXED_HEADER_SIZE: usize = 9;
+
+/// Length of the fixed part of a recipient slot: key ID and wrapped key length
+const SLOT_HEADER_SIZE: usize = KEY_ID_SIZE + 2;
+
+/// Nonce for sealing the content key under a key derived by RSA-KEM, which is used only once
+const KEY_WRAP_NONCE: [u8; aead::NONCE_SIZE] = [0; aead::NONCE_SIZE];
+
+/// Returns the ID of `key` as written in an envelope header: the first 16 bytes of the SHA-256 hash
+/// of n and e, each preceded by its length as 4 big-endian bytes
+///
+/// # Arguments
+/// * `key` - Public key of a recipient
+pub fn key_id(key: &RsaPublicKey) -> [u8; KEY_ID_SIZE] {
+    let mut encoded = Vec::new();
+    for part in [&key.n, &key.e].iter() {
+        let bytes = part.to_bytes_be();
+        encoded.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        encoded.extend_from_slice(&bytes);
+    }
+
+    let mut id = [0_u8; KEY_ID_SIZE];
+    id.copy_from_slice(&HashAlgorithm::Sha256.digest(&encoded)[..KEY_ID_SIZE]);
+    id
+}
+
+/// Helper function, wraps content key `key` for `pubkey`. With OAEP the key itself is encrypted;
+/// with RSA-KEM, which can only make a new key, the key made is used to seal `key` with
+/// ChaCha20-Poly1305, as in RFC 5990's KEM with key wrap.
+fn _wrap_key<R: RandomSource>(key: &[u8; aead::KEY_SIZE], pubkey: &RsaPublicKey, id: &[u8; KEY_ID_SIZE],
+                              wrap: KeyWrap, rng: &mut R) -> Result<Vec<u8>, Error> {
+    match wrap {
+        KeyWrap::Oaep(hash) => rsa::encrypt_oaep(key, pubkey, hash, &[], rng),
+        KeyWrap::Kem(_) => {
+            let (wrapping_key, mut wrapped) = hybrid::_encapsulate(pubkey, wrap, rng)?;
+            wrapped.extend(aead::seal(&wrapping_key, &KEY_WRAP_NONCE, id, key));
+            Ok(wrapped)
+        },
+    }
+}
+
+/// Helper function, returns the content key wrapped by `_wrap_key`
+fn _unwrap_key(privkey: &RsaPrivateKey, id: &[u8; KEY_ID_SIZE], wrap: KeyWrap,
+               wrapped: &[u8]) -> Result<[u8; aead::KEY_SIZE], Error> {
+    match wrap {
+        KeyWrap::Oaep(_) => hybrid::_decapsulate(privkey, wrap, wrapped),
+        KeyWrap::Kem(_) => {
+            if wrapped.len() != privkey.size() + aead::KEY_SIZE + aead::TAG_SIZE {
+                return Err(Error::Decryption);
+            }
+            let (encapsulated, sealed) = wrapped.split_at(privkey.size());
+            let wrapping_key = hybrid::_decapsulate(privkey, wrap, encapsulated)?;
+            let unwrapped = aead::open(&wrapping_key, &KEY_WRAP_NONCE, id, sealed)?;
+
+            let mut key = [0_u8; aead::KEY_SIZE];
+            key.copy_from_slice(&unwrapped);
+            Ok(key)
+        },
+    }
+}
+
+/// Helper function, returns the error of this crate that caused I/O error `error`, or `otherwise` if
+/// it has another cause
+fn _cause(error: io::Error, otherwise: Error) -> Error {
+    error.into_inner()
+        .and_then(|inner| inner.downcast::<Error>().ok())
+        .map_or(otherwise, |cause| *cause)
+}
+
+/// Returns a `stream::Encryptor` for an envelope: a streamed container whose content key is wrapped
+/// separately for each of `recipients`, so that any one of their private keys can decrypt it. The
+/// header lists each recipient's `key_id` next to their wrapped key, so a recipient finds theirs
+/// without trying to unwrap the others. Fails with `InvalidInput` if there are no recipients, more
+/// than 65535, or a key is too small for OAEP with the chosen hash.
+///
+/// # Arguments
+/// * `inner` - Where to write the envelope
+/// * `recipients` - Public keys of the recipients, e.g. read with `io::read_public_key_from_file`
+/// * `wrap` - How to wrap the content key for each recipient
+/// * `rng` - Random number generator for the content key, nonce and padding
+pub fn encryptor<W: Write, R: RandomSource>(inner: W, recipients: &[RsaPublicKey], wrap: KeyWrap,
+                                            rng: &mut R) -> io::Result<Encryptor<W>> {
+    if recipients.is_empty() || recipients.len() > u16::MAX as usize {
+        return Err(stream::_io_error(Error::InvalidParameters));
+    }
+    let mut key = [0_u8; aead::KEY_SIZE];
+    rng.fill_bytes(&mut key);
+
+    let (wrap_id, hash_id) = hybrid::_wrap_ids(wrap);
+    let mut header = hybrid::MAGIC.to_vec();
+    header.extend_from_slice(&[hybrid::ENVELOPE_VERSION, wrap_id, hash_id]);
+    header.extend_from_slice(&(recipients.len() as u16).to_be_bytes());
+    for pubkey in recipients {
+        let id = key_id(pubkey);
+        let wrapped = _wrap_key(&key, pubkey, &id, wrap, rng).map_err(stream::_io_error)?;
+        header.extend_from_slice(&id);
+        header.extend_from_slice(&(wrapped.len() as u16).to_be_bytes());
+        header.extend_from_slice(&wrapped);
+    }
+
+    let mut nonce = [0_u8; aead::NONCE_SIZE];
+    rng.fill_bytes(&mut nonce);
+    header.extend_from_slice(&nonce);
+    Encryptor::_with_header(inner, key, nonce, header)
+}
+
+/// Returns a `stream::Decryptor` for an envelope written by `encryptor`, having read the header from
+/// `inner` and unwrapped the content key from the slot whose ID matches `privkey`. Fails with
+/// `InvalidData` if it isn't an envelope or has been altered, with `Error::NotRecipient` as the
+/// cause if no slot is for `privkey`.
+///
+/// # Arguments
+/// * `inner` - Where to read the envelope from
+/// * `privkey` - The private key of one of the recipients
+pub fn decryptor<R: Read>(mut inner: R, privkey: &RsaPrivateKey) -> io::Result<Decryptor<R>> {
+    let mut header = vec![0_u8; FIXED_HEADER_SIZE];
+    inner.read_exact(&mut header)?;
+    if header[..4] != hybrid::MAGIC || header[4] != hybrid::ENVELOPE_VERSION {
+        return Err(stream::_io_error(Error::Decryption));
+    }
+    let wrap = hybrid::_wrap_from_ids(header[5], header[6]).ok_or_else(|| stream::_io_error(Error::Decryption))?;
+    let recipients = u16::from_be_bytes([header[7], header[8]]);
+
+    // Read every slot, since the chunks follow them, but only unwrap the one for this key
+    let id = key_id(&privkey.to_public_key());
+    let mut key = None;
+    for _ in 0..recipients {
+        let start = header.len();
+        header.resize(start + SLOT_HEADER_SIZE, 0);
+        inner.read_exact(&mut header[start..])?;
+        let wrapped_len = u16::from_be_bytes([header[start + KEY_ID_SIZE], header[start + KEY_ID_SIZE + 1]]) as usize;
+        header.resize(start + SLOT_HEADER_SIZE + wrapped_len, 0);
+        inner.read_exact(&mut header[start + SLOT_HEADER_SIZE..])?;
+
+        if key.is_none() && header[start..start + KEY_ID_SIZE] == id {
+            let wrapped = &header[start + SLOT_HEADER_SIZE..];
+            key = Some(_unwrap_key(privkey, &id, wrap, wrapped).map_err(stream::_io_error)?);
+        }
+    }
+    let key = key.ok_or_else(|| stream::_io_error(Error::NotRecipient))?;
+
+    let mut nonce = [0_u8; aead::NONCE_SIZE];
+    inner.read_exact(&mut nonce)?;
+    header.extend_from_slice(&nonce);
+    Ok(Decryptor::_with_header(inner, key, nonce, header))
+}
+
+/// Encrypts everything read from `input` for each of `recipients` with `encryptor`, writing the
+/// envelope to `output`
+///
+/// # Arguments
+/// * `input` - Where to read the message from
+/// * `output` - Where to write the envelope
+/// * `recipients` - Public keys of the recipients
+/// * `wrap` - How to wrap the content key for each recipient
+/// * `rng` - Random number generator for the content key, nonce and padding
+pub fn encrypt_stream<I: Read, O: Write, R: RandomSource>(mut input: I, output: O, recipients: &[RsaPublicKey],
+                                                          wrap: KeyWrap, rng: &mut R) -> io::Result<()> {
+    let mut encryptor = encryptor(output, recipients, wrap, rng)?;
+    io::copy(&mut input, &mut encryptor)?;
+    encryptor.finish()?;
+    Ok(())
+}
+
+/// Decrypts an envelope read from `input` with `decryptor`, writing the message to `output`. On
+/// error, what was written to `output` is genuine but incomplete.
+///
+/// # Arguments
+/// * `input` - Where to read the envelope from
+/// * `output` - Where to write the message
+/// * `privkey` - The private key of one of the recipients
+pub fn decrypt_stream<I: Read, O: Write>(input: I, mut output: O, privkey: &RsaPrivateKey) -> io::Result<()> {
+    let mut decryptor = decryptor(input, privkey)?;
+    io::copy(&mut decryptor, &mut output)?;
+    output.flush()
+}
+
+/// Encrypts `msg` for each of `recipients`, returning the envelope. See `encryptor`.
+///
+/// # Arguments
+/// * `msg` - Bytes to encrypt
+/// * `recipients` - Public keys of the recipients
+/// * `wrap` - How to wrap the content key for each recipient
+/// * `rng` - Random number generator for the content key, nonce and padding
+pub fn encrypt<R: RandomSource>(msg: &[u8], recipients: &[RsaPublicKey], wrap: KeyWrap,
+                                rng: &mut R) -> Result<Vec<u8>, Error> {
+    let mut envelope = Vec::new();
+    encrypt_stream(msg, &mut envelope, recipients, wrap, rng).map_err(|e| _cause(e, Error::InvalidParameters))?;
+    Ok(envelope)
+}
+
+/// Decrypts an envelope produced by `encrypt` or `encryptor`. Fails with `Error::NotRecipient` if
+/// `privkey` isn't one of the recipients, or `Error::Decryption` if it isn't an envelope or has been
+/// altered.
+///
+/// # Arguments
+/// * `envelope` - Bytes to decrypt
+/// * `privkey` - The private key of one of the recipients
+pub fn decrypt(envelope: &[u8], privkey: &RsaPrivateKey) -> Result<Vec<u8>, Error> {
+    let mut msg = Vec::new();
+    decryptor(envelope, privkey)
+        .and_then(|mut decryptor| decryptor.read_to_end(&mut msg))
+        .map_err(|e| _cause(e, Error::Decryption))?;
+    Ok(msg)
+}
@@ -0,0 +1,347 @@
+use std::time::{Duration, Instant};
+
+use num::BigUint;
+use num::Integer;
+use num::traits::{One, Pow, Zero};
+
+use crate::ecm;
+use crate::primes::{self, PrimalityTest};
+use crate::rand::RandomSource;
+use crate::siqs::{self, SieveProgress};
+
+/// `factorise` trial divides by every prime below this before trying anything else
+const TRIAL_DIVISION_LIMIT: u64 = 1 << 16;
+
+/// Starting effort for each method in `factorise`, doubled every round
+const INITIAL_EFFORT: u64 = 1 << 10;
+
+/// Effort at which `factorise` stops doubling the cheap methods and moves on to ECM and the quadratic
+/// sieve, for numbers big enough to use them
+const QUICK_EFFORT: u64 = 1 << 12;
+
+/// Pollard's rho multiplies this many differences together between gcds
+const RHO_BATCH: u64 = 128;
+
+/// ECM parameters for finding factors of up to each number of digits: stage 1 bound and number of
+/// curves (from the GMP-ECM documentation). Stage 2 goes up to 100 times the stage 1 bound.
+const ECM_LEVELS: [(usize, u64, usize); 5] = [
+    (15, 2000, 25),
+    (20, 11000, 90),
+    (25, 50000, 300),
+    (30, 250000, 700),
+    (35, 1000000, 1800),
+];
+
+/// Method that found a prime factor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// Division by small primes
+    TrialDivision,
+    /// Fermat's method, which is fast when the two factors are close together
+    Fermat,
+    /// Pollard's rho with Brent's cycle detection
+    PollardRho,
+    /// Pollard's p - 1, which is fast when p - 1 has only small prime factors for some factor p
+    PollardPMinusOne,
+    /// Lenstra's elliptic curve method, which is fast when some factor is small
+    EllipticCurve,
+    /// The self-initialising quadratic sieve, whose speed depends only on the size of the number
+    QuadraticSieve,
+    /// Taking a root of a perfect power, which the other methods can be slow or unable to split
+    PerfectPower,
+    /// The number was prime to begin with, and so needed no splitting
+    PrimalityTest,
+}
+
+/// A prime factor and the method that split it off
+#[derive(Clone, Debug, PartialEq)]
+pub struct Factor {
+    pub prime: BigUint,
+    pub method: Method,
+}
+
+/// Progress of `factorise_with_progress` through its slower methods
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// ECM is starting curve number `curve` with stage 1 bound `b1`
+    Curve { curve: usize, b1: u64 },
+    /// The quadratic sieve has collected more relations
+    Sieve(SieveProgress),
+}
+
+/// Result of `factorise`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Factorisation {
+    /// Prime factors found, with repeats, in increasing order
+    pub factors: Vec<Factor>,
+    /// Composite parts that could not be split within the time budget, or 0 if that was the number,
+    /// since it has no factorisation into primes
+    pub unfactored: Vec<BigUint>,
+}
+
+impl Factorisation {
+    /// Returns whether the number was completely factored
+    pub fn is_complete(&self) -> bool {
+        self.unfactored.is_empty()
+    }
+}
+
+/// Returns the smallest prime factor of `n` up to `limit`, if it has one other than itself
+pub fn trial_division(n: &BigUint, limit: u64) -> Option<BigUint> {
+    primes::primes_in_range(2, limit.saturating_add(1))
+        .map(BigUint::from)
+        .take_while(|p| p < n)
+        .find(|p| (n % p).is_zero())
+}
+
+/// Returns a factor of odd `n` found by Fermat's method, writing n = a^2 - b^2 = (a - b)(a + b) for
+/// a from the square root of `n` upwards. Gives up after `max_iterations` values of a.
+pub fn fermat(n: &BigUint, max_iterations: u64) -> Option<BigUint> {
+    let one: BigUint = One::one();
+    if n.is_even() {
+        return if *n > BigUint::from(2_u32) { Some(BigUint::from(2_u32)) } else { None };
+    }
+
+    let mut a = n.sqrt();
+    if &a * &a < *n {
+        a += &one;
+    }
+    // b_squared = a^2 - n, updated as a increases using (a + 1)^2 = a^2 + 2a + 1
+    let mut b_squared = &a * &a - n;
+    for _ in 0..max_iterations {
+        let b = b_squared.sqrt();
+        if &b * &b == b_squared {
+            // a - b = 1 only when n = a + b is prime
+            let factor = &a - &b;
+            return if factor > one { Some(factor) } else { None };
+        }
+        b_squared += (&a << 1) + &one;
+        a += &one;
+    }
+
+    None
+}
+
+/// Returns a factor of `n` found by Pollard's rho with Brent's cycle detection, iterating
+/// x -> x^2 + c mod n from a random start and random c. Gives up after about `max_iterations`
+/// steps, or if the sequence cycles modulo `n` itself; trying again draws a new c.
+pub fn pollard_rho<R: RandomSource>(n: &BigUint, max_iterations: u64, rng: &mut R) -> Option<BigUint> {
+    let one: BigUint = One::one();
+    if *n <= BigUint::from(3_u32) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigUint::from(2_u32));
+    }
+
+    let c = rng.gen_bigint_range(&one, &(n - &one));
+    let step = |x: &BigUint| (x * x + &c) % n;
+    let distance = |a: &BigUint, b: &BigUint| if a > b { a - b } else { b - a };
+
+    let mut y = rng.gen_bigint_range(&one, n);
+    let mut x = y.clone();
+    let mut saved_y = y.clone();
+    let mut product = one.clone();
+    let mut g = one.clone();
+    let mut cycle_length = 1;
+    let mut iterations = 0;
+
+    // Compare y against x, the value at the last power of two, in batches, multiplying the
+    // differences together so that each batch needs only one gcd
+    while g == one {
+        x = y.clone();
+        for _ in 0..cycle_length {
+            y = step(&y);
+        }
+
+        let mut k = 0;
+        while k < cycle_length && g == one {
+            saved_y = y.clone();
+            for _ in 0..RHO_BATCH.min(cycle_length - k) {
+                y = step(&y);
+                product = product * distance(&x, &y) % n;
+            }
+            g = product.gcd(n);
+            k += RHO_BATCH;
+        }
+
+        iterations += 2 * cycle_length;
+        if g == one && iterations > max_iterations {
+            return None;
+        }
+        cycle_length *= 2;
+    }
+
+    // The batch overshot to a multiple of n, so step through it one at a time
+    if g == *n {
+        loop {
+            saved_y = step(&saved_y);
+            g = distance(&x, &saved_y).gcd(n);
+            if g != one {
+                break;
+            }
+        }
+    }
+
+    if g == *n { None } else { Some(g) }
+}
+
+/// Returns a factor p of `n` found by Pollard's p - 1, which works when every prime power dividing
+/// p - 1 is at most `bound`
+pub fn pollard_p_minus_one(n: &BigUint, bound: u64) -> Option<BigUint> {
+    let one: BigUint = One::one();
+    if n.is_even() {
+        return if *n > BigUint::from(2_u32) { Some(BigUint::from(2_u32)) } else { None };
+    }
+
+    // a = 2^M mod n, where M is the product of the largest power of each prime up to bound
+    let mut a = BigUint::from(2_u32);
+    for p in primes::primes_in_range(2, bound.saturating_add(1)) {
+        let mut power = p;
+        while let Some(next) = power.checked_mul(p).filter(|next| *next <= bound) {
+            power = next;
+        }
+        a = a.modpow(&BigUint::from(power), n);
+    }
+
+    let g = ((a + n - &one) % n).gcd(n);
+    if g > one && g < *n { Some(g) } else { None }
+}
+
+/// Returns r and k with `n` = r^k for the largest k > 1 there is, if `n` is a perfect power
+pub fn perfect_power(n: &BigUint) -> Option<(BigUint, u32)> {
+    (2..n.bits() as u32).rev().find_map(|k| {
+        let root = n.nth_root(k);
+        if root.clone().pow(k) == *n { Some((root, k)) } else { None }
+    })
+}
+
+/// Helper function, splits composite `n` into two factors by the fastest method likely to work before
+/// `deadline`. Perfect powers are split by taking a root. The cheap methods go first with doubling effort, each round cut down to what should fit
+/// in the time left. Numbers big enough for the quadratic sieve then get enough ECM curves to find any
+/// factor up to a quarter of their length, and then the sieve; numbers too big for it get ever longer
+/// ECM runs instead.
+fn _split<R, F>(n: &BigUint, deadline: Instant, rng: &mut R, progress: &mut F) -> Option<(BigUint, Method)>
+    where R: RandomSource, F: FnMut(Progress) {
+    // The quadratic sieve can't split perfect powers, and Fermat and rho are slow on big ones
+    if let Some((root, _)) = perfect_power(n) {
+        return Some((root, Method::PerfectPower));
+    }
+
+    let bits = n.bits();
+    let sieve = (siqs::MIN_SIEVE_BITS..=siqs::MAX_SIEVE_BITS).contains(&bits);
+
+    let mut effort = INITIAL_EFFORT;
+    while Instant::now() < deadline && (bits < siqs::MIN_SIEVE_BITS || effort <= QUICK_EFFORT) {
+        let round_start = Instant::now();
+        if let Some(factor) = fermat(n, effort) {
+            return Some((factor, Method::Fermat));
+        }
+        if let Some(factor) = pollard_p_minus_one(n, effort * 16) {
+            return Some((factor, Method::PollardPMinusOne));
+        }
+        if let Some(factor) = pollard_rho(n, effort * 16, rng) {
+            return Some((factor, Method::PollardRho));
+        }
+
+        let round = round_start.elapsed().as_secs_f64();
+        let remaining = deadline.saturating_duration_since(Instant::now()).as_secs_f64();
+        let affordable = (effort as f64 * remaining / round.max(1e-6)) as u64;
+        effort = (2 * effort).min(affordable).max(1);
+    }
+    if bits < siqs::MIN_SIEVE_BITS {
+        return None;
+    }
+
+    let digits = (bits as f64 * 2_f64.log10()) as usize;
+    for (level, (max_digits, b1, curves)) in ECM_LEVELS.iter().enumerate() {
+        if sieve && *max_digits > digits / 4 {
+            break;
+        }
+        // Without the sieve to fall back on, the last level runs until the deadline
+        let curves = if !sieve && level == ECM_LEVELS.len() - 1 { usize::MAX } else { *curves };
+        for curve in 1..=curves {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            progress(Progress::Curve { curve, b1: *b1 });
+            if let Some(factor) = ecm::ecm(n, *b1, 100 * b1, 1, rng) {
+                return Some((factor, Method::EllipticCurve));
+            }
+        }
+    }
+
+    if sieve {
+        let budget = deadline.saturating_duration_since(Instant::now());
+        return siqs::quadratic_sieve(n, budget, rng, |report| progress(Progress::Sieve(*report)))
+            .map(|factor| (factor, Method::QuadraticSieve));
+    }
+    None
+}
+
+/// Factors `n` into primes, giving up on any part that is still composite after `budget`. Small
+/// factors are removed by trial division first; perfect powers are split by taking roots, and other
+/// composites by Fermat's method, Pollard's p - 1 and Pollard's rho, each given more effort in turn,
+/// and then if need be by ECM and the quadratic sieve. Each factor records the method that split it
+/// off.
+///
+/// # Arguments
+/// * `n` - Number to factor
+/// * `budget` - Time allowed
+/// * `rng` - Random number generator for the randomised methods and the primality tests
+pub fn factorise<R: RandomSource>(n: &BigUint, budget: Duration, rng: &mut R) -> Factorisation {
+    factorise_with_progress(n, budget, rng, |_| {})
+}
+
+/// Same as `factorise`, but calls `progress` as ECM and the quadratic sieve go along, so that long
+/// runs can be watched or charted
+///
+/// # Arguments
+/// * `n` - Number to factor
+/// * `budget` - Time allowed
+/// * `rng` - Random number generator for the randomised methods and the primality tests
+/// * `progress` - Called with each ECM curve started and each batch of sieve relations
+pub fn factorise_with_progress<R, F>(n: &BigUint, budget: Duration, rng: &mut R, mut progress: F) -> Factorisation
+    where R: RandomSource, F: FnMut(Progress) {
+    let deadline = Instant::now() + budget;
+    let mut factors = Vec::new();
+    let mut unfactored = Vec::new();
+
+    let mut rest = n.clone();
+    for p in primes::primes_in_range(2, TRIAL_DIVISION_LIMIT) {
+        let p = BigUint::from(p);
+        if &p * &p > rest {
+            break;
+        }
+        while (&rest % &p).is_zero() {
+            rest /= &p;
+            factors.push(Factor { prime: p.clone(), method: Method::TrialDivision });
+        }
+    }
+
+    let mut parts = vec![(rest, if factors.is_empty() { Method::PrimalityTest } else { Method::TrialDivision })];
+    while let Some((part, method)) = parts.pop() {
+        if part.is_one() {
+            continue;
+        }
+        if part.is_zero() {
+            unfactored.push(part);
+            continue;
+        }
+        if primes::is_prime(&part, PrimalityTest::BailliePsw, rng) {
+            factors.push(Factor { prime: part, method });
+            continue;
+        }
+
+        match _split(&part, deadline, rng, &mut progress) {
+            Some((factor, method)) => {
+                parts.push((&part / &factor, method));
+                parts.push((factor, method));
+            },
+            None => unfactored.push(part),
+        }
+    }
+
+    factors.sort_by(|a, b| a.prime.cmp(&b.prime));
+    Factorisation { factors, unfactored }
+}
@@ -0,0 +1,460 @@
+use std::cmp::min;
+
+/// Initial hash value for SHA-1 (FIPS 180-4 section 5.3.1)
+const SHA1_INIT: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+/// Initial hash value for SHA-256 (FIPS 180-4 section 5.3.3)
+const SHA256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// SHA-256 round constants (FIPS 180-4 section 4.2.2)
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Initial hash value for SHA-512 (FIPS 180-4 section 5.3.5)
+const SHA512_INIT: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+/// Initial hash value for SHA-384 (FIPS 180-4 section 5.3.4)
+const SHA384_INIT: [u64; 8] = [
+    0xcbbb9d5dc1059ed8, 0x629a292a367cd507, 0x9159015a3070dd17, 0x152fecd8f70e5939,
+    0x67332667ffc00b31, 0x8eb44a8768581511, 0xdb0c2e0d64f98fa7, 0x47b5481dbefa4fa4,
+];
+
+/// SHA-512 round constants (FIPS 180-4 section 4.2.3)
+const SHA512_K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+/// A hash function that can be fed data incrementally. Implemented by the hashes in this module, and
+/// by anything else that should be usable with the padding schemes in `pkcs1`.
+pub trait Digest {
+    /// Returns the length of the digest in bytes
+    fn output_len(&self) -> usize;
+
+    /// Feeds `data` into the hash
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the digest of everything fed in since the last reset, and resets the hash so it can
+    /// be reused
+    fn finalize_reset(&mut self) -> Vec<u8>;
+
+    /// Returns the digest of `data` on its own, resetting the hash afterwards
+    fn digest(&mut self, data: &[u8]) -> Vec<u8> {
+        self.update(data);
+        self.finalize_reset()
+    }
+}
+
+/// The hash functions implemented in this module, for choosing one at runtime
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Returns a new hasher for this algorithm
+    pub fn hasher(&self) -> Box<dyn Digest> {
+        match self {
+            HashAlgorithm::Sha1 => Box::new(Sha1::new()),
+            HashAlgorithm::Sha256 => Box::new(Sha256::new()),
+            HashAlgorithm::Sha384 => Box::new(Sha384::new()),
+            HashAlgorithm::Sha512 => Box::new(Sha512::new()),
+        }
+    }
+
+    /// Returns the length of this algorithm's digest in bytes
+    pub fn output_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+
+    /// Returns the digest of `data`
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        self.hasher().digest(data)
+    }
+}
+
+/// Helper function, appends `data` to the partial block in `buffer`, calling `compress` on every
+/// block that is completed
+///
+/// # Arguments
+/// * `buffer` - Bytes left over from previous updates, always shorter than `block_len`
+/// * `block_len` - Size of the blocks taken by `compress`, in bytes
+/// * `data` - New data to hash
+/// * `compress` - Compression function, called with each full block in order
+fn _update_blocks<F: FnMut(&[u8])>(buffer: &mut Vec<u8>, block_len: usize, mut data: &[u8], mut compress: F) {
+    if !buffer.is_empty() {
+        let take = min(block_len - buffer.len(), data.len());
+        buffer.extend_from_slice(&data[..take]);
+        data = &data[take..];
+        if buffer.len() < block_len {
+            return;
+        }
+        compress(buffer);
+        buffer.clear();
+    }
+
+    let mut blocks = data.chunks_exact(block_len);
+    for block in &mut blocks {
+        compress(block);
+    }
+    buffer.extend_from_slice(blocks.remainder());
+}
+
+/// Helper function, returns the Merkle-Damgard padding for a message of `len` bytes: a single 1 bit,
+/// zeros up to the end of the last block, then the message length in bits as a `len_bytes` integer
+fn _padding(len: u128, block_len: usize, len_bytes: usize) -> Vec<u8> {
+    let used = (len % block_len as u128) as usize;
+    let zeros = (2 * block_len - used - 1 - len_bytes) % block_len;
+
+    let mut pad = vec![0x80_u8];
+    pad.extend(vec![0_u8; zeros]);
+    pad.extend_from_slice(&(len * 8).to_be_bytes()[16 - len_bytes..]);
+    pad
+}
+
+fn _sha1_compress(state: &mut [u32; 5], block: &[u8]) {
+    let mut w = [0_u32; 80];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for (i, wi) in w.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5a827999),
+            20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*wi);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = t;
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+        *s = s.wrapping_add(*v);
+    }
+}
+
+fn _sha256_compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0_u32; 64];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (k, wi) in SHA256_K.iter().zip(w.iter()) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(*k).wrapping_add(*wi);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *s = s.wrapping_add(*v);
+    }
+}
+
+fn _sha512_compress(state: &mut [u64; 8], block: &[u8]) {
+    let mut w = [0_u64; 80];
+    for (i, word) in block.chunks_exact(8).enumerate() {
+        let mut bytes = [0_u8; 8];
+        bytes.copy_from_slice(word);
+        w[i] = u64::from_be_bytes(bytes);
+    }
+    for i in 16..80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (k, wi) in SHA512_K.iter().zip(w.iter()) {
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(*k).wrapping_add(*wi);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *s = s.wrapping_add(*v);
+    }
+}
+
+/// SHA-1 (FIPS 180-4). Only suitable where collision resistance isn't needed, such as OAEP and MGF1.
+#[derive(Clone)]
+pub struct Sha1 {
+    state: [u32; 5],
+    buffer: Vec<u8>,
+    len: u64,
+}
+
+impl Sha1 {
+    pub fn new() -> Sha1 {
+        Sha1 { state: SHA1_INIT, buffer: Vec::with_capacity(64), len: 0 }
+    }
+}
+
+impl Default for Sha1 {
+    fn default() -> Sha1 {
+        Sha1::new()
+    }
+}
+
+impl Digest for Sha1 {
+    fn output_len(&self) -> usize {
+        20
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let Sha1 { state, buffer, len } = self;
+        *len += data.len() as u64;
+        _update_blocks(buffer, 64, data, |block| _sha1_compress(state, block));
+    }
+
+    fn finalize_reset(&mut self) -> Vec<u8> {
+        let padding = _padding(self.len as u128, 64, 8);
+        self.update(&padding);
+
+        let res = self.state.iter().flat_map(|word| word.to_be_bytes().to_vec()).collect();
+        *self = Sha1::new();
+        res
+    }
+}
+
+/// SHA-256 (FIPS 180-4)
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: Vec<u8>,
+    len: u64,
+}
+
+impl Sha256 {
+    pub fn new() -> Sha256 {
+        Sha256 { state: SHA256_INIT, buffer: Vec::with_capacity(64), len: 0 }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Sha256 {
+        Sha256::new()
+    }
+}
+
+impl Digest for Sha256 {
+    fn output_len(&self) -> usize {
+        32
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let Sha256 { state, buffer, len } = self;
+        *len += data.len() as u64;
+        _update_blocks(buffer, 64, data, |block| _sha256_compress(state, block));
+    }
+
+    fn finalize_reset(&mut self) -> Vec<u8> {
+        let padding = _padding(self.len as u128, 64, 8);
+        self.update(&padding);
+
+        let res = self.state.iter().flat_map(|word| word.to_be_bytes().to_vec()).collect();
+        *self = Sha256::new();
+        res
+    }
+}
+
+/// SHA-512 (FIPS 180-4)
+#[derive(Clone)]
+pub struct Sha512 {
+    state: [u64; 8],
+    buffer: Vec<u8>,
+    len: u128,
+}
+
+impl Sha512 {
+    pub fn new() -> Sha512 {
+        Sha512 { state: SHA512_INIT, buffer: Vec::with_capacity(128), len: 0 }
+    }
+}
+
+impl Default for Sha512 {
+    fn default() -> Sha512 {
+        Sha512::new()
+    }
+}
+
+impl Digest for Sha512 {
+    fn output_len(&self) -> usize {
+        64
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let Sha512 { state, buffer, len } = self;
+        *len += data.len() as u128;
+        _update_blocks(buffer, 128, data, |block| _sha512_compress(state, block));
+    }
+
+    fn finalize_reset(&mut self) -> Vec<u8> {
+        let padding = _padding(self.len, 128, 16);
+        self.update(&padding);
+
+        let res = self.state.iter().flat_map(|word| word.to_be_bytes().to_vec()).collect();
+        *self = Sha512::new();
+        res
+    }
+}
+
+/// SHA-384 (FIPS 180-4), SHA-512 with a different initial value and a truncated output
+#[derive(Clone)]
+pub struct Sha384 {
+    inner: Sha512,
+}
+
+impl Sha384 {
+    pub fn new() -> Sha384 {
+        Sha384 { inner: Sha512 { state: SHA384_INIT, buffer: Vec::with_capacity(128), len: 0 } }
+    }
+}
+
+impl Default for Sha384 {
+    fn default() -> Sha384 {
+        Sha384::new()
+    }
+}
+
+impl Digest for Sha384 {
+    fn output_len(&self) -> usize {
+        48
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    fn finalize_reset(&mut self) -> Vec<u8> {
+        let mut res = self.inner.finalize_reset();
+        res.truncate(48);
+        *self = Sha384::new();
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [HashAlgorithm; 4] = [HashAlgorithm::Sha1, HashAlgorithm::Sha256, HashAlgorithm::Sha384,
+                                            HashAlgorithm::Sha512];
+
+    /// Formats bytes as a hex string
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn abc_matches_fips_180_4() {
+        let expected = [
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd\
+             454d4423643ce80e2a9ac94fa54ca49f",
+        ];
+        for (algorithm, expected) in ALGORITHMS.iter().zip(expected.iter()) {
+            let digest = algorithm.digest(b"abc");
+            assert_eq!(digest.len(), algorithm.output_len());
+            assert_eq!(hex(&digest), *expected, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn million_a_matches_fips_180_4() {
+        let expected = [
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f",
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+            "9d0e1809716474cb086e834e310a4a1ced149e9c00f248527972cec5704c2a5b07b8b3dc38ecc4ebae97ddd87f3d8985",
+            "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973ebde0ff244877ea60a4cb0432ce577c31b\
+             eb009c5c2c49aa2e4eadb217ad8cc09b",
+        ];
+        let chunk = [b'a'; 1000];
+        for (algorithm, expected) in ALGORITHMS.iter().zip(expected.iter()) {
+            // Fed in pieces that don't line up with the blocks, to cover the buffering as well
+            let mut hasher = algorithm.hasher();
+            for _ in 0..1000 {
+                hasher.update(&chunk[..333]);
+                hasher.update(&chunk[333..]);
+            }
+            assert_eq!(hex(&hasher.finalize_reset()), *expected, "{:?}", algorithm);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::io::Read;
+use num::traits::Zero;
+
+use crate::aead;
+use crate::hash::HashAlgorithm;
+use crate::rand::RandomSource;
+use crate::rsa;
+use crate::rsa::{Error, RsaPrivateKey, RsaPublicKey};
+use crate::{envelope, stream};
+
+/// First bytes of every container, to tell them apart from other files
+pub const MAGIC: [u8; 4] = *b"RSVH";
+
+/// Version of the container format written by `encrypt`
+const VERSION: u8 = 1;
+
+/// Version of the container format written by `stream::Encryptor`, which splits the message into
+/// separately authenticated chunks
+pub(crate) const STREAM_VERSION: u8 = 2;
+
+/// Version of the container format written by `envelope`, which wraps the content key for several
+/// recipients
+pub(crate) const ENVELOPE_VERSION: u8 = 3;
+
+/// Length of the fixed part of the header: magic, version, key wrap, hash and wrapped key length
+pub(crate) const FIXED_HEADER_SIZE: usize = 9;
+
+/// How the content key of a container is protected with RSA
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyWrap {
+    /// A random content key is encrypted with RSAES-OAEP using the given hash
+    Oaep(HashAlgorithm),
+    /// RSA-KEM (RFC 5990): a random number below n is encrypted as it is, and the content key is
+    /// derived from it with KDF2 over the given hash. Works with keys of any size, and has no padding
+    /// that could be checked wrongly.
+    Kem(HashAlgorithm),
+}
+
+impl Default for KeyWrap {
+    fn default() -> KeyWrap {
+        KeyWrap::Kem(HashAlgorithm::Sha256)
+    }
+}
+
+/// The parts of a container header
+pub(crate) struct Header {
+    /// Version of the container format
+    pub(crate) version: u8,
+    /// How the content key is wrapped
+    pub(crate) wrap: KeyWrap,
+    /// The wrapped content key, as long as the modulus
+    pub(crate) wrapped_key: Vec<u8>,
+    /// Nonce for ChaCha20-Poly1305, or for streams the nonce that each chunk's nonce is derived from
+    pub(crate) nonce: [u8; aead::NONCE_SIZE],
+}
+
+/// Helper function, returns the byte identifying `hash` in a header
+fn _hash_id(hash: HashAlgorithm) -> u8 {
+    match hash {
+        HashAlgorithm::Sha1 => 1,
+        HashAlgorithm::Sha256 => 2,
+        HashAlgorithm::Sha384 => 3,
+        HashAlgorithm::Sha512 => 4,
+    }
+}
+
+/// Helper function, returns the hash identified by byte `id` in a header
+fn _hash_from_id(id: u8) -> Option<HashAlgorithm> {
+    match id {
+        1 => Some(HashAlgorithm::Sha1),
+        2 => Some(HashAlgorithm::Sha256),
+        3 => Some(HashAlgorithm::Sha384),
+        4 => Some(HashAlgorithm::Sha512),
+        _ => None,
+    }
+}
+
+/// Helper function, returns the bytes identifying `wrap` and its hash in a header
+pub(crate) fn _wrap_ids(wrap: KeyWrap) -> (u8, u8) {
+    match wrap {
+        KeyWrap::Oaep(hash) => (1, _hash_id(hash)),
+        KeyWrap::Kem(hash) => (2, _hash_id(hash)),
+    }
+}
+
+/// Helper function, returns the key wrap identified by bytes `wrap_id` and `hash_id` in a header
+pub(crate) fn _wrap_from_ids(wrap_id: u8, hash_id: u8) -> Option<KeyWrap> {
+    let hash = _hash_from_id(hash_id)?;
+    match wrap_id {
+        1 => Some(KeyWrap::Oaep(hash)),
+        2 => Some(KeyWrap::Kem(hash)),
+        _ => None,
+    }
+}
+
+/// Helper function, derives `len` bytes from `secret` with KDF2 (ANSI X9.44, ISO 18033-2), hashing
+/// the secret with a counter starting from 1
+fn _kdf2(secret: &[u8], len: usize, hash: HashAlgorithm) -> Vec<u8> {
+    let mut digest = hash.hasher();
+    let mut output = Vec::with_capacity(len + hash.output_len());
+    let mut counter: u32 = 1;
+    while output.len() < len {
+        digest.update(secret);
+        digest.update(&counter.to_be_bytes());
+        output.extend(digest.finalize_reset());
+        counter += 1;
+    }
+
+    output.truncate(len);
+    output
+}
+
+/// Helper function, returns a new content key and the key wrapped for `pubkey`
+pub(crate) fn _encapsulate<R: RandomSource>(pubkey: &RsaPublicKey, wrap: KeyWrap,
+                                 rng: &mut R) -> Result<([u8; aead::KEY_SIZE], Vec<u8>), Error> {
+    let mut key = [0_u8; aead::KEY_SIZE];
+    match wrap {
+        KeyWrap::Oaep(hash) => {
+            rng.fill_bytes(&mut key);
+            let wrapped = rsa::encrypt_oaep(&key, pubkey, hash, &[], rng)?;
+            Ok((key, wrapped))
+        },
+        KeyWrap::Kem(hash) => {
+            let secret = rng.gen_bigint_range(&Zero::zero(), &pubkey.n);
+            let wrapped = rsa::i2osp(&rsa::rsaep(&secret, pubkey), pubkey.size())
+                .expect("Cipher is always smaller than the modulus");
+            let secret = rsa::i2osp(&secret, pubkey.size()).expect("Secret is smaller than the modulus");
+            key.copy_from_slice(&_kdf2(&secret, aead::KEY_SIZE, hash));
+            Ok((key, wrapped))
+        },
+    }
+}
+
+/// Helper function, returns the content key wrapped by `_encapsulate`
+pub(crate) fn _decapsulate(privkey: &RsaPrivateKey, wrap: KeyWrap, wrapped: &[u8]) -> Result<[u8; aead::KEY_SIZE], Error> {
+    let mut key = [0_u8; aead::KEY_SIZE];
+    match wrap {
+        KeyWrap::Oaep(hash) => {
+            let unwrapped = rsa::decrypt_oaep(wrapped, privkey, hash, &[])?;
+            if unwrapped.len() != aead::KEY_SIZE {
+                return Err(Error::Decryption);
+            }
+            key.copy_from_slice(&unwrapped);
+        },
+        KeyWrap::Kem(hash) => {
+            let cipher = rsa::os2ip(wrapped);
+            if wrapped.len() != privkey.size() || cipher >= privkey.n {
+                return Err(Error::Decryption);
+            }
+            let secret = rsa::i2osp(&rsa::rsadp(&cipher, privkey), privkey.size()).ok_or(Error::Decryption)?;
+            key.copy_from_slice(&_kdf2(&secret, aead::KEY_SIZE, hash));
+        },
+    }
+    Ok(key)
+}
+
+/// Helper function, returns the header for a container, which is also authenticated as additional
+/// data
+pub(crate) fn _write_header(header: &Header) -> Vec<u8> {
+    let (wrap_id, hash_id) = _wrap_ids(header.wrap);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(header.version);
+    bytes.push(wrap_id);
+    bytes.push(hash_id);
+    bytes.extend_from_slice(&(header.wrapped_key.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&header.wrapped_key);
+    bytes.extend_from_slice(&header.nonce);
+    bytes
+}
+
+/// Helper function, reads the header at the start of `container`, returning it and its length.
+/// Fails with `Error::Decryption` if it isn't a header of a version this can read.
+pub(crate) fn _read_header(container: &[u8]) -> Result<(Header, usize), Error> {
+    if container.len() < FIXED_HEADER_SIZE || container[..4] != MAGIC {
+        return Err(Error::Decryption);
+    }
+    let version = container[4];
+    if version != VERSION && version != STREAM_VERSION {
+        return Err(Error::Decryption);
+    }
+    let wrap = _wrap_from_ids(container[5], container[6]).ok_or(Error::Decryption)?;
+
+    let wrapped_len = u16::from_be_bytes([container[7], container[8]]) as usize;
+    let header_len = FIXED_HEADER_SIZE + wrapped_len + aead::NONCE_SIZE;
+    if container.len() < header_len {
+        return Err(Error::Decryption);
+    }
+    let mut nonce = [0_u8; aead::NONCE_SIZE];
+    nonce.copy_from_slice(&container[header_len - aead::NONCE_SIZE..header_len]);
+
+    let header = Header {
+        version,
+        wrap,
+        wrapped_key: container[FIXED_HEADER_SIZE..FIXED_HEADER_SIZE + wrapped_len].to_vec(),
+        nonce,
+    };
+    Ok((header, header_len))
+}
+
+/// Returns whether `bytes` starts like a container written by `encrypt`, `stream::Encryptor` or
+/// `envelope`
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Encrypts `msg` of any length under `pubkey` by hybrid encryption. A random 256-bit content key
+/// encrypts the message with ChaCha20-Poly1305, and only the content key is encrypted with RSA, as
+/// chosen by `wrap`. The result is a binary container: the header (magic bytes, version, key wrap,
+/// wrapped key and nonce) followed by the cipher and tag. The header is authenticated along with
+/// the message, so a container that has been altered anywhere fails to decrypt.
+///
+/// # Arguments
+/// * `msg` - Bytes to encrypt
+/// * `pubkey` - Public key of the recipient
+/// * `wrap` - How to wrap the content key. Fails with `Error::MessageTooLong` if the key is too
+///   small for OAEP with the chosen hash.
+/// * `rng` - Random number generator for the content key, nonce and padding
+pub fn encrypt<R: RandomSource>(msg: &[u8], pubkey: &RsaPublicKey, wrap: KeyWrap,
+                                rng: &mut R) -> Result<Vec<u8>, Error> {
+    let (key, wrapped_key) = _encapsulate(pubkey, wrap, rng)?;
+    let mut nonce = [0_u8; aead::NONCE_SIZE];
+    rng.fill_bytes(&mut nonce);
+
+    let mut container = _write_header(&Header { version: VERSION, wrap, wrapped_key, nonce });
+    let sealed = aead::seal(&key, &nonce, &container, msg);
+    container.extend(sealed);
+    Ok(container)
+}
+
+/// Decrypts a container produced by `encrypt`, or by `stream::Encryptor` or `envelope` if it fits in
+/// memory. Fails with `Error::Decryption` if it isn't a container, was encrypted for another key, or
+/// has been altered.
+///
+/// # Arguments
+/// * `container` - Bytes to decrypt
+/// * `privkey` - The private key to use for decryption
+pub fn decrypt(container: &[u8], privkey: &RsaPrivateKey) -> Result<Vec<u8>, Error> {
+    if is_container(container) && container.get(4) == Some(&ENVELOPE_VERSION) {
+        return envelope::decrypt(container, privkey);
+    }
+    let (header, header_len) = _read_header(container)?;
+    if header.version == STREAM_VERSION {
+        let mut msg = Vec::new();
+        stream::Decryptor::new(container, privkey)
+            .and_then(|mut decryptor| decryptor.read_to_end(&mut msg))
+            .map_err(|_| Error::Decryption)?;
+        return Ok(msg);
+    }
+
+    let key = _decapsulate(privkey, header.wrap, &header.wrapped_key)?;
+
+    let (header_bytes, sealed) = container.split_at(header_len);
+    aead::open(&key, &header.nonce, header_bytes, sealed)
+}
+
@@ -3,7 +3,10 @@ use std::io::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use num::{BigUint};
-use crate::rsa;
+use crate::{rand, rsa, hybrid, stream, envelope};
+use crate::hybrid::KeyWrap;
+use crate::rsa::{RsaPublicKey, RsaPrivateKey, CrtParams};
+use crate::primes::{PrimeCertificate, PocklingtonStep};
 
 #[derive(Serialize, Deserialize)]
 struct PublicKey {
@@ -13,7 +16,17 @@ struct PublicKey {
 
 #[derive(Serialize, Deserialize)]
 struct PrivateKey {
-    d: BigUint
+    d: BigUint,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    p: Option<BigUint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    q: Option<BigUint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dp: Option<BigUint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dq: Option<BigUint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    qinv: Option<BigUint>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,20 +34,40 @@ struct Cipher {
     msg: Vec<BigUint>
 }
 
-pub fn write_json_to_disk(key: ((BigUint, BigUint), BigUint)) {
-    let pub_key = json!({
-        "n": (key.0).0,
-        "e": (key.0).1
+#[derive(Serialize, Deserialize)]
+struct Signature {
+    sig: Vec<u8>
+}
+
+#[derive(Serialize, Deserialize)]
+struct CertificateStep {
+    prime: BigUint,
+    witness: BigUint,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Certificate {
+    base: u32,
+    steps: Vec<CertificateStep>,
+}
+
+/// Writes the public half of `key` to `pub_key.txt` and the private half to `priv_key.txt`
+pub fn write_json_to_disk(key: &RsaPrivateKey) {
+    let pub_key = json!(PublicKey {
+        n: key.n.clone(),
+        e: key.e.clone(),
     });
-    // println!("key n: {:?}", (key.0).0);
-    // println!("json: {}", pub_key.to_string());
     _write_json_to_disk(&pub_key, "pub_key.txt").expect("Something went wrong writing the file");
 
-    let priv_key = json!({
-        "d": key.1
+    let crt = key.crt.clone();
+    let priv_key = json!(PrivateKey {
+        d: key.d.clone(),
+        p: key.p.clone(),
+        q: key.q.clone(),
+        dp: crt.as_ref().map(|crt| crt.dp.clone()),
+        dq: crt.as_ref().map(|crt| crt.dq.clone()),
+        qinv: crt.map(|crt| crt.qinv),
     });
-    // println!("key d: {:?}", key.1);
-    // println!("json: {}", priv_key.to_string());
     _write_json_to_disk(&priv_key, "priv_key.txt").expect("Something went wrong writing the file");
 }
 
@@ -44,18 +77,36 @@ fn _write_json_to_disk(key: &Value, path: &str) -> std::io::Result<()> {
     Ok(())
 }
 
-pub fn read_key_from_disk() -> std::io::Result<((BigUint, BigUint), BigUint)> {
-    let pub_key_str = fs::read_to_string("pub_key.txt")?;
+/// Reads the public key stored in `pub_key.txt`
+pub fn read_public_key_from_disk() -> std::io::Result<RsaPublicKey> {
+    read_public_key_from_file("pub_key.txt")
+}
+
+/// Reads a public key written by `write_json_to_disk` from `src_path`, so that keys saved under other
+/// names can be loaded together
+pub fn read_public_key_from_file(src_path: &str) -> std::io::Result<RsaPublicKey> {
+    let pub_key_str = fs::read_to_string(src_path)?;
     let pub_key: PublicKey = serde_json::from_str(&pub_key_str)?;
-    // println!("json: {:?}", pub_key.n);
+
+    Ok(RsaPublicKey::new(pub_key.n, pub_key.e))
+}
+
+/// Reads the key pair stored in `pub_key.txt` and `priv_key.txt`. Key files without the primes or
+/// CRT values still load, but the resulting key uses the slower non-CRT decryption until it is
+/// upgraded with `RsaPrivateKey::with_primes`.
+pub fn read_key_from_disk() -> std::io::Result<RsaPrivateKey> {
+    let pub_key = read_public_key_from_disk()?;
     let priv_key_str = fs::read_to_string("priv_key.txt")?;
     let priv_key: PrivateKey = serde_json::from_str(&priv_key_str)?;
-    // println!("json: {:?}", priv_key.d);
 
-    Ok(((pub_key.n, pub_key.e), priv_key.d))
+    let mut key = RsaPrivateKey::new(pub_key.n, pub_key.e, priv_key.d, priv_key.p, priv_key.q);
+    if let (Some(dp), Some(dq), Some(qinv)) = (priv_key.dp, priv_key.dq, priv_key.qinv) {
+        key.crt = Some(CrtParams { dp, dq, qinv });
+    }
+    Ok(key)
 }
 
-pub fn write_cipher_to_disk(cipher: &Vec<BigUint>, dest_path: &str) {
+pub fn write_cipher_to_disk(cipher: &[BigUint], dest_path: &str) {
     let cipher_json = json!({
         "msg": cipher
     });
@@ -71,31 +122,138 @@ pub fn read_cipher_from_disk(src_path: &str) -> std::io::Result<Vec<BigUint>>{
     Ok(cipher.msg)
 }
 
-pub fn encrypt_file(src_path: &str, dest_path: &str, pub_key: (BigUint, BigUint)) {
-    let msg = fs::read_to_string(src_path)
-        .expect("Something went wrong reading the file");
-    let encrypted_msg = rsa::encrypt_str(&msg, pub_key);
+pub fn write_signature_to_disk(sig: &[u8], dest_path: &str) -> std::io::Result<()> {
+    let sig_json = json!(Signature { sig: sig.to_vec() });
+
+    _write_json_to_disk(&sig_json, dest_path)
+}
+
+pub fn read_signature_from_disk(src_path: &str) -> std::io::Result<Vec<u8>> {
+    let sig_str = fs::read_to_string(src_path)?;
+    let sig: Signature = serde_json::from_str(&sig_str)?;
+
+    Ok(sig.sig)
+}
+
+/// Writes prime certificates to `dest_path` as a JSON list
+pub fn write_certificates_to_disk(certificates: &[PrimeCertificate], dest_path: &str) -> std::io::Result<()> {
+    let certificates: Vec<Certificate> = certificates.iter().map(|certificate| Certificate {
+        base: certificate.base,
+        steps: certificate.steps.iter().map(|step| CertificateStep {
+            prime: step.prime.clone(),
+            witness: step.witness.clone(),
+        }).collect(),
+    }).collect();
 
+    _write_json_to_disk(&json!(certificates), dest_path)
+}
+
+/// Reads prime certificates written by `write_certificates_to_disk`. They are not checked; use
+/// `primes::verify_certificate` for that.
+pub fn read_certificates_from_disk(src_path: &str) -> std::io::Result<Vec<PrimeCertificate>> {
+    let certificates_str = fs::read_to_string(src_path)?;
+    let certificates: Vec<Certificate> = serde_json::from_str(&certificates_str)?;
+
+    Ok(certificates.into_iter().map(|certificate| PrimeCertificate {
+        base: certificate.base,
+        steps: certificate.steps.into_iter().map(|step| PocklingtonStep {
+            prime: step.prime,
+            witness: step.witness,
+        }).collect(),
+    }).collect())
+}
+
+/// Path that stands for standard input or standard output in place of a file
+pub const STDIO_PATH: &str = "-";
+
+/// Opens the file at `src_path` for reading, or standard input if it is `-`
+pub fn open_input(src_path: &str) -> std::io::Result<Box<dyn Read>> {
+    if src_path == STDIO_PATH {
+        Ok(Box::new(std::io::stdin()))
+    } else {
+        Ok(Box::new(File::open(src_path)?))
+    }
+}
+
+/// Creates the file at `dest_path` for writing, or writes to standard output if it is `-`
+pub fn create_output(dest_path: &str) -> std::io::Result<Box<dyn Write>> {
+    if dest_path == STDIO_PATH {
+        Ok(Box::new(std::io::stdout()))
+    } else {
+        Ok(Box::new(File::create(dest_path)?))
+    }
+}
+
+/// Encrypts the file at `src_path` for `pub_key`, writing the cipher to `dest_path`; either may be
+/// `-` for standard input or output. Files longer than one block, and anything read from standard
+/// input, are streamed through `stream::Encryptor` with the default key wrap, so memory use doesn't
+/// grow with the file. Shorter files are encrypted with `rsa::encrypt`, and the cipher blocks
+/// written as JSON. Any file round-trips exactly, text or not.
+pub fn encrypt_file(src_path: &str, dest_path: &str, pub_key: &RsaPublicKey) -> std::io::Result<()> {
+    let fits_one_block = src_path != STDIO_PATH
+        && fs::metadata(src_path)?.len() <= rsa::single_block_message_len(pub_key) as u64;
+
+    if !fits_one_block {
+        return stream::encrypt_stream(open_input(src_path)?, create_output(dest_path)?, pub_key,
+                                      KeyWrap::default(), &mut rand::new_secure());
+    }
+
+    let msg = fs::read(src_path)?;
+    let encrypted_msg: Vec<BigUint> = rsa::encrypt(&msg, pub_key).map_err(stream::_io_error)?
+        .chunks(pub_key.size()).map(rsa::os2ip).collect();
     let cipher = json!({
         "msg": encrypted_msg
     });
 
-    _write_json_to_disk(&cipher, dest_path).expect("Something went wrong reading the file");
+    let mut dest = create_output(dest_path)?;
+    dest.write_all(cipher.to_string().as_ref())?;
+    dest.flush()
 }
 
-pub fn decrypt_file(src_path: &str, dest_path: &str, priv_key: BigUint, pub_key: BigUint) -> std::io::Result<()> {
-    let cipher_str = fs::read_to_string(src_path)
-        .expect("Something went wrong reading the file");
-    let cipher: Cipher = serde_json::from_str(&cipher_str).unwrap();
+/// Encrypts the file at `src_path` for every key in `pub_keys` with `envelope::encryptor`, so that
+/// any of the matching private keys can decrypt it, writing the envelope to `dest_path`. Either path
+/// may be `-` for standard input or output. The file is streamed, so memory use doesn't grow with it.
+pub fn encrypt_file_for_recipients(src_path: &str, dest_path: &str, pub_keys: &[RsaPublicKey]) -> std::io::Result<()> {
+    envelope::encrypt_stream(open_input(src_path)?, create_output(dest_path)?, pub_keys,
+                             KeyWrap::default(), &mut rand::new_secure())
+}
 
-    // TODO: make this work :o
-    println!("cipher message");
-    for thing in cipher.msg.clone() {
-        println!("{}", thing);
+/// Decrypts a file written by `encrypt_file`, `encrypt_file_for_recipients` or `hybrid::encrypt`,
+/// in any format, writing the original file to `dest_path`; either may be `-` for standard input or
+/// output. Streamed containers and envelopes are decrypted a chunk at a time, so if one turns out
+/// to be altered or cut short, what was written to `dest_path` before the error is genuine but
+/// incomplete.
+pub fn decrypt_file(src_path: &str, dest_path: &str, priv_key: &RsaPrivateKey) -> std::io::Result<()> {
+    let invalid = |e: rsa::Error| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+
+    // The magic bytes and version tell the formats apart
+    let mut input = open_input(src_path)?;
+    let mut start = Vec::new();
+    input.by_ref().take(hybrid::MAGIC.len() as u64 + 1).read_to_end(&mut start)?;
+    let version = if hybrid::is_container(&start) { start.last().copied() } else { None };
+    let mut input = std::io::Cursor::new(start).chain(input);
+
+    if version == Some(hybrid::STREAM_VERSION) {
+        return stream::decrypt_stream(input, create_output(dest_path)?, priv_key);
+    }
+    if version == Some(hybrid::ENVELOPE_VERSION) {
+        return envelope::decrypt_stream(input, create_output(dest_path)?, priv_key);
     }
 
-    let decrypted_msg = rsa::decrypt_str(&cipher.msg, priv_key, pub_key);
-    let mut dest = File::create(dest_path)?;
-    dest.write_all(decrypted_msg.as_ref())?;
-    Ok(())
+    let mut encrypted = Vec::new();
+    input.read_to_end(&mut encrypted)?;
+    let decrypted_msg = if hybrid::is_container(&encrypted) {
+        hybrid::decrypt(&encrypted, priv_key).map_err(invalid)?
+    } else {
+        let cipher: Cipher = serde_json::from_slice(&encrypted)?;
+        let mut cipher_bytes = Vec::new();
+        for block in &cipher.msg {
+            cipher_bytes.extend(rsa::i2osp(block, priv_key.size()).ok_or_else(|| invalid(rsa::Error::Decryption))?);
+        }
+        rsa::decrypt(&cipher_bytes, priv_key).map_err(invalid)?
+    };
+
+    let mut dest = create_output(dest_path)?;
+    dest.write_all(&decrypted_msg)?;
+    dest.flush()
 }
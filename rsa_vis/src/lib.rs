@@ -0,0 +1,20 @@
+extern crate num;
+
+pub mod primes;
+pub mod test;
+pub mod rand;
+pub mod rsa;
+pub mod vis;
+pub mod io;
+pub mod pkcs1;
+pub mod hash;
+pub mod signature;
+pub mod chacha;
+pub mod factor;
+pub mod ecm;
+pub mod siqs;
+pub mod attacks;
+pub mod aead;
+pub mod hybrid;
+pub mod stream;
+pub mod envelope;
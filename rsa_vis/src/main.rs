@@ -1,14 +1,13 @@
-extern crate num;
+extern crate rsa_vis;
 // extern crate azul;
 
-mod primes;
-mod test;
-mod rand;
-mod rsa;
-mod vis;
 mod cli;
-mod io;
 
 fn main() {
-    cli::init_cli_interface();
-}
\ No newline at end of file
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        cli::init_cli_interface();
+    } else {
+        std::process::exit(cli::run_command(&args));
+    }
+}
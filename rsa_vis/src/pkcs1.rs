@@ -0,0 +1,200 @@
+use crate::rand::RandomSource;
+use crate::hash::Digest;
+use crate::rsa::{self, Error, RsaPublicKey, RsaPrivateKey};
+
+/// Minimum number of random padding bytes required by RSAES-PKCS1-v1_5
+const PKCS1V15_MIN_PADDING: usize = 8;
+
+/// Returns the length in bytes of the longest message that can be encrypted in a single
+/// RSAES-PKCS1-v1_5 block under `key`
+pub fn pkcs1v15_max_message_len(key: &RsaPublicKey) -> usize {
+    key.size().saturating_sub(PKCS1V15_MIN_PADDING + 3)
+}
+
+/// Encrypts `msg` with RSAES-PKCS1-v1_5 (RFC 8017 section 7.2.1), returning a cipher the same length
+/// as the modulus
+///
+/// # Arguments
+/// * `msg` - Message to encrypt, at most `pkcs1v15_max_message_len(key)` bytes long
+/// * `key` - Public key to encrypt with
+/// * `rng` - Random number generator used for the non-zero padding bytes
+pub fn encrypt_pkcs1v15<R: RandomSource>(msg: &[u8], key: &RsaPublicKey, rng: &mut R) -> Result<Vec<u8>, Error> {
+    let k = key.size();
+    if k < PKCS1V15_MIN_PADDING + 3 || msg.len() > pkcs1v15_max_message_len(key) {
+        return Err(Error::MessageTooLong);
+    }
+
+    // EM = 0x00 || 0x02 || PS || 0x00 || M, where PS is made up of non-zero random bytes
+    let mut em = vec![0_u8; k];
+    em[1] = 0x02;
+    let ps_len = k - msg.len() - 3;
+    rng.fill_bytes(&mut em[2..2 + ps_len]);
+    for byte in em[2..2 + ps_len].iter_mut() {
+        while *byte == 0 {
+            let mut replacement = [0_u8];
+            rng.fill_bytes(&mut replacement);
+            *byte = replacement[0];
+        }
+    }
+    em[k - msg.len()..].copy_from_slice(msg);
+
+    let c = rsa::rsaep(&rsa::os2ip(&em), key);
+    Ok(rsa::i2osp(&c, k).expect("Cipher is always smaller than the modulus"))
+}
+
+/// Decrypts a cipher produced by `encrypt_pkcs1v15` (RFC 8017 section 7.2.2). Every kind of failure
+/// returns the same `Error::Decryption`, and the padding is checked in full before deciding, so the
+/// result gives away as little as possible about the decrypted block.
+///
+/// # Arguments
+/// * `cipher` - Cipher to decrypt, exactly as long as the modulus
+/// * `key` - Private key to decrypt with
+pub fn decrypt_pkcs1v15(cipher: &[u8], key: &RsaPrivateKey) -> Result<Vec<u8>, Error> {
+    let k = key.size();
+    if k < PKCS1V15_MIN_PADDING + 3 || cipher.len() != k {
+        return Err(Error::Decryption);
+    }
+    let c = rsa::os2ip(cipher);
+    if c >= key.n {
+        return Err(Error::Decryption);
+    }
+    let em = rsa::i2osp(&rsa::rsadp(&c, key), k).ok_or(Error::Decryption)?;
+
+    // Find the first zero byte after the padding without stopping early
+    let mut invalid = (em[0] != 0) as u8 | (em[1] != 0x02) as u8;
+    let mut found_separator = 0_u8;
+    let mut separator = 0_usize;
+    for (i, byte) in em.iter().enumerate().skip(2) {
+        let is_first_zero = (*byte == 0) as u8 & !found_separator & 1;
+        separator |= i * is_first_zero as usize;
+        found_separator |= is_first_zero;
+    }
+    invalid |= !found_separator & 1;
+    invalid |= (separator < 2 + PKCS1V15_MIN_PADDING) as u8;
+
+    if invalid != 0 {
+        return Err(Error::Decryption);
+    }
+    Ok(em[separator + 1..].to_vec())
+}
+
+/// Helper function, XORs `mask` into `data` in place
+fn _xor_in_place(data: &mut [u8], mask: &[u8]) {
+    for (d, m) in data.iter_mut().zip(mask.iter()) {
+        *d ^= m;
+    }
+}
+
+/// Mask generation function MGF1 (RFC 8017 appendix B.2.1). Returns a mask of `mask_len` bytes
+/// derived from `seed` by hashing it together with an incrementing counter.
+pub fn mgf1(seed: &[u8], mask_len: usize, digest: &mut dyn Digest) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(mask_len + digest.output_len());
+    let mut counter: u32 = 0;
+    while mask.len() < mask_len {
+        digest.update(seed);
+        digest.update(&counter.to_be_bytes());
+        mask.extend(digest.finalize_reset());
+        counter += 1;
+    }
+
+    mask.truncate(mask_len);
+    mask
+}
+
+/// Returns the length in bytes of the longest message that can be encrypted in a single RSAES-OAEP
+/// block under `key` using `digest`
+pub fn oaep_max_message_len(key: &RsaPublicKey, digest: &dyn Digest) -> usize {
+    key.size().saturating_sub(2 * digest.output_len() + 2)
+}
+
+/// Encrypts `msg` with RSAES-OAEP (RFC 8017 section 7.1.1) using MGF1 over the same hash, returning
+/// a cipher the same length as the modulus
+///
+/// # Arguments
+/// * `msg` - Message to encrypt, at most `oaep_max_message_len(key, digest)` bytes long
+/// * `key` - Public key to encrypt with
+/// * `digest` - Hash used for the label and for MGF1
+/// * `label` - Label associated with the message. Pass an empty slice for no label.
+/// * `rng` - Random number generator used for the seed
+pub fn encrypt_oaep<R: RandomSource>(msg: &[u8], key: &RsaPublicKey, digest: &mut dyn Digest, label: &[u8],
+                                     rng: &mut R) -> Result<Vec<u8>, Error> {
+    let k = key.size();
+    let h_len = digest.output_len();
+    if k < 2 * h_len + 2 || msg.len() > oaep_max_message_len(key, digest) {
+        return Err(Error::MessageTooLong);
+    }
+
+    // DB = lHash || PS || 0x01 || M, where PS is made up of zero bytes
+    let mut db = digest.digest(label);
+    db.extend(vec![0_u8; k - msg.len() - 2 * h_len - 2]);
+    db.push(0x01);
+    db.extend_from_slice(msg);
+
+    let mut seed = vec![0_u8; h_len];
+    rng.fill_bytes(&mut seed);
+    let db_mask = mgf1(&seed, k - h_len - 1, digest);
+    _xor_in_place(&mut db, &db_mask);
+    let seed_mask = mgf1(&db, h_len, digest);
+    _xor_in_place(&mut seed, &seed_mask);
+
+    // EM = 0x00 || maskedSeed || maskedDB
+    let mut em = vec![0_u8];
+    em.extend(seed);
+    em.extend(db);
+
+    let c = rsa::rsaep(&rsa::os2ip(&em), key);
+    Ok(rsa::i2osp(&c, k).expect("Cipher is always smaller than the modulus"))
+}
+
+/// Decrypts a cipher produced by `encrypt_oaep` (RFC 8017 section 7.1.2). As with
+/// `decrypt_pkcs1v15`, every kind of failure returns the same `Error::Decryption` and the whole
+/// block is checked before deciding.
+///
+/// # Arguments
+/// * `cipher` - Cipher to decrypt, exactly as long as the modulus
+/// * `key` - Private key to decrypt with
+/// * `digest` - Hash used when encrypting
+/// * `label` - Label used when encrypting
+pub fn decrypt_oaep(cipher: &[u8], key: &RsaPrivateKey, digest: &mut dyn Digest,
+                    label: &[u8]) -> Result<Vec<u8>, Error> {
+    let k = key.size();
+    let h_len = digest.output_len();
+    if k < 2 * h_len + 2 || cipher.len() != k {
+        return Err(Error::Decryption);
+    }
+    let c = rsa::os2ip(cipher);
+    if c >= key.n {
+        return Err(Error::Decryption);
+    }
+    let em = rsa::i2osp(&rsa::rsadp(&c, key), k).ok_or(Error::Decryption)?;
+
+    let l_hash = digest.digest(label);
+    let mut seed = em[1..1 + h_len].to_vec();
+    let mut db = em[1 + h_len..].to_vec();
+    let seed_mask = mgf1(&db, h_len, digest);
+    _xor_in_place(&mut seed, &seed_mask);
+    let db_mask = mgf1(&seed, k - h_len - 1, digest);
+    _xor_in_place(&mut db, &db_mask);
+
+    let mut invalid = (em[0] != 0) as u8;
+    for (a, b) in db.iter().zip(l_hash.iter()) {
+        invalid |= (a != b) as u8;
+    }
+
+    // Find the 0x01 separator after PS, treating any other non-zero byte before it as invalid
+    let mut looking = 1_u8;
+    let mut separator = 0_usize;
+    for (i, byte) in db.iter().enumerate().skip(h_len) {
+        let is_one = (*byte == 0x01) as u8;
+        let is_zero = (*byte == 0) as u8;
+        separator |= i * (looking & is_one) as usize;
+        invalid |= looking & !is_one & !is_zero & 1;
+        looking &= !is_one & 1;
+    }
+    invalid |= looking;
+
+    if invalid != 0 {
+        return Err(Error::Decryption);
+    }
+    Ok(db[separator + 1..].to_vec())
+}
@@ -1,20 +1,77 @@
-use crate::rand;
+use crate::rand::RandomSource;
+use crate::rsa;
+
+use std::sync::OnceLock;
 
 use num::BigUint;
 use num::FromPrimitive;
 use num::traits::{Zero, One};
+use num::Integer;
 
 const MILLER_RABIN_ACCURACY: usize = 30;
 
+/// Candidates are trial divided by every odd prime up to this limit before any Miller-Rabin rounds
+const SMALL_PRIME_LIMIT: usize = 4096;
+
+/// Windows of candidates are sieved by every odd prime up to this limit
+const SIEVE_PRIME_LIMIT: usize = 65536;
+
+/// Number of odd candidates sieved at once by the incremental search
+const SIEVE_WINDOW: usize = 4096;
+
+/// Provable primes of up to this many bits are found and checked by trial division, and start the
+/// certificate chains of larger ones
+const PROVABLE_BASE_BITS: usize = 32;
+
+/// Result of a search for a pair of RSA primes, along with how many candidates were drawn for each
+#[derive(Clone, Debug, PartialEq)]
+pub struct RsaPrimePair {
+    pub p: BigUint,
+    pub q: BigUint,
+    /// Number of candidates drawn before `p` was found, including `p` itself
+    pub p_candidates: usize,
+    /// Number of candidates drawn before `q` was found, including `q` itself
+    pub q_candidates: usize,
+}
+
+/// One link of a `PrimeCertificate`. `prime - 1` is a multiple of the previous prime in the chain,
+/// which is greater than the square root of `prime`, and `witness` satisfies Pocklington's criterion.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PocklingtonStep {
+    pub prime: BigUint,
+    pub witness: BigUint,
+}
+
+/// Proof that a number is prime: a chain of primes starting from one small enough to check by trial
+/// division, where each of the rest is proven prime by Pocklington's criterion using the one before
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrimeCertificate {
+    /// First prime of the chain
+    pub base: u32,
+    /// Steps proving each larger prime, in increasing order
+    pub steps: Vec<PocklingtonStep>,
+}
+
+impl PrimeCertificate {
+    /// Returns the prime the certificate proves, which is the last one in the chain
+    pub fn prime(&self) -> BigUint {
+        match self.steps.last() {
+            Some(step) => step.prime.clone(),
+            None => BigUint::from(self.base),
+        }
+    }
+}
+
 /// Return a list of prime numbers in the range of [2,n]
 ///
 /// # Arguments
 ///
 /// * `n` - A usize specifying range in which to generate primes
 pub fn sieve_of_eratosthenes(n: usize) -> std::vec::Vec<u32> {
-    // Mark elements that are multiples of primes
+    // Mark elements that are multiples of primes. Every composite up to n has a prime factor of at
+    // most sqrt(n), which must be included.
     let mut vals = vec![true; n+1];
-    for i in 2..((n as f64).sqrt() as usize) {
+    for i in 2..=(_isqrt(n as u64) as usize) {
         if vals[i] {
             let mut j = i*i;
             while j < n+1 {
@@ -26,32 +83,33 @@ pub fn sieve_of_eratosthenes(n: usize) -> std::vec::Vec<u32> {
 
     // Build result list, only returning elements that are not composite
     let mut res= Vec::new();
-    for i in 2..(n+1) {
-        if vals[i] {
+    for (i, is_prime) in vals.iter().enumerate().skip(2) {
+        if *is_prime {
             res.push(i as u32);
         }
     }
-    return res;
+    res
 }
 
 
-/// Return a list of prime numbers in the range of [2,n]
+/// Return a list of prime numbers in the range of [2,limit]
 ///
 /// # Arguments
 ///
-/// * `n` - A usize specifying range in which to generate primes
+/// * `limit` - A usize specifying range in which to generate primes
 pub fn sieve_of_atkin(limit: usize) -> std::vec::Vec<u32> {
     // This code adapted from GeeksForGeeks: https://www.geeksforgeeks.org/sieve-of-atkin/
     let mut res = Vec::new();
-    if limit > 2 {
+    if limit >= 2 {
         res.push(2);
     }
-    if limit > 3 {
+    if limit >= 3 {
         res.push(3);
     }
 
-    // Initialise the sieve array with false values
-    let mut sieve = vec![false; limit];
+    // Initialise the sieve array with false values, with an entry for every number up to and
+    // including limit
+    let mut sieve = vec![false; limit + 1];
 
     /* Mark sieve[n] as true if one
        of the following is true:
@@ -64,25 +122,26 @@ pub fn sieve_of_atkin(limit: usize) -> std::vec::Vec<u32> {
        solutions and n % 12 = 7
     c) n = (3*x*x)-(y*y) has odd number of
        solutions, x > y and n % 12 = 11 */
-    let mut x:i32 = 1;
-    let limit32:i32 = limit as i32;
-    while x*x < limit32 {
+    let mut x: usize = 1;
+    while x*x <= limit {
         let mut y = 1;
-        while y*y < limit32 {
+        while y*y <= limit {
             // Main part of Sieve of Atkin
-            let mut n:i32 = (4 * x * x) + (y * y);
-            if n <= limit32 && (n % 12 == 1 || n % 12 == 5) {
-                sieve[n as usize] ^= true;
+            let mut n = (4 * x * x) + (y * y);
+            if n <= limit && (n % 12 == 1 || n % 12 == 5) {
+                sieve[n] ^= true;
             }
 
             n = (3 * x * x) + (y * y);
-            if n <= limit32 && n % 12 == 7 {
-                sieve[n as usize] ^= true;
+            if n <= limit && n % 12 == 7 {
+                sieve[n] ^= true;
             }
 
-            n = (3 * x * x) - (y * y);
-            if x > y && n <= limit32 && n % 12 == 11 {
-                sieve[n as usize] ^= true;
+            if x > y {
+                n = (3 * x * x) - (y * y);
+                if n <= limit && n % 12 == 11 {
+                    sieve[n] ^= true;
+                }
             }
             y += 1;
         }
@@ -91,11 +150,11 @@ pub fn sieve_of_atkin(limit: usize) -> std::vec::Vec<u32> {
 
     // Mark all multiples of squares as non-prime
     let mut r = 5;
-    while r*r < limit32 {
-        if sieve[r as usize] {
+    while r*r <= limit {
+        if sieve[r] {
             let mut i = r*r;
-            while i < limit32 {
-                sieve[i as usize] = false;
+            while i <= limit {
+                sieve[i] = false;
                 i += r * r;
             }
         }
@@ -103,74 +162,849 @@ pub fn sieve_of_atkin(limit: usize) -> std::vec::Vec<u32> {
     }
 
     // Append i to res if sieve[i] is true
-    for i in 5..limit32 {
-        if sieve[i as usize] {
+    for (i, is_prime) in sieve.iter().enumerate().skip(5) {
+        if *is_prime {
             res.push(i as u32);
         }
     }
 
-    return res;
+    res
+}
+
+
+/// Numbers below 30 that are coprime to 30. In the wheel sieve, bit `i` of byte `b` stands for the
+/// number 30b + WHEEL[i]; multiples of 2, 3 and 5 are never stored.
+const WHEEL: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+
+/// Bytes in each segment of the wheel sieve, covering 30 numbers each. Small enough to stay in
+/// cache while it is sieved.
+const SEGMENT_BYTES: usize = 128 * 1024;
+
+/// Helper function, returns the bit standing for `residue` mod 30 in a wheel sieve byte, for
+/// residues in `WHEEL`
+fn _wheel_bit(residue: u64) -> u32 {
+    match residue {
+        1 => 0,
+        7 => 1,
+        11 => 2,
+        13 => 3,
+        17 => 4,
+        19 => 5,
+        23 => 6,
+        29 => 7,
+        _ => unreachable!("Residue is coprime to 30"),
+    }
+}
+
+/// Helper function, returns the largest integer whose square is at most `n`
+fn _isqrt(n: u64) -> u64 {
+    let mut root = (n as f64).sqrt() as u64;
+    while root.checked_mul(root).is_none_or(|square| square > n) {
+        root -= 1;
+    }
+    while (root + 1).checked_mul(root + 1).is_some_and(|square| square <= n) {
+        root += 1;
+    }
+    root
+}
+
+/// Iterator over the primes in a range, made by `primes_in_range`. The range is sieved one
+/// segment at a time, with one bit for each number coprime to 30, so memory use depends on the
+/// end of the range rather than its length. See `primes_in_range` for what that costs.
+pub struct SegmentedSieve {
+    lo: u64,
+    hi: u64,
+    /// Primes 2, 3 and 5 that fall in the range, which the wheel skips
+    wheel_primes: std::vec::IntoIter<u64>,
+    /// Primes from 7 up to the square root of `hi`, which sieve each segment
+    base_primes: Vec<u32>,
+    /// Index of the first byte of `segment`
+    segment_start: u64,
+    /// One past the index of the last byte of the range
+    end_byte: u64,
+    /// Current segment. Bits are cleared as their primes are returned.
+    segment: Vec<u8>,
+    /// Next byte of `segment` to scan
+    position: usize,
+}
+
+impl SegmentedSieve {
+    /// Helper function, moves on to the next segment and sieves it. Returns false once the whole
+    /// range has been sieved.
+    fn _next_segment(&mut self) -> bool {
+        self.segment_start += self.segment.len() as u64;
+        if self.segment_start >= self.end_byte {
+            return false;
+        }
+        let len = SEGMENT_BYTES.min((self.end_byte - self.segment_start) as usize);
+        self.segment.clear();
+        self.segment.resize(len, 0xff);
+        self.position = 0;
+
+        // The end of the last segment may not fit in a u64, but nothing past u64::MAX is in range
+        let segment_lo = 30 * self.segment_start;
+        let segment_hi = segment_lo.saturating_add(30 * len as u64);
+        if self.segment_start == 0 {
+            // 1 is not prime
+            self.segment[0] &= !1;
+        }
+
+        for p in &self.base_primes {
+            let p = u64::from(*p);
+            if p * p >= segment_hi {
+                break;
+            }
+            // Multiples p * k with k on the wheel. Stepping k by 30 moves p bytes along and keeps
+            // the same bit, so each of the eight residues of k is one strided pass.
+            let k_min = p.max(segment_lo.div_ceil(p));
+            let k_residue = k_min % 30;
+            for w in WHEEL.iter() {
+                let multiple = match (k_min + (w + 30 - k_residue) % 30).checked_mul(p) {
+                    Some(multiple) if multiple < segment_hi => multiple,
+                    _ => continue,
+                };
+                let mask = !(1_u8 << _wheel_bit(multiple % 30));
+                let mut byte = (multiple / 30 - self.segment_start) as usize;
+                while byte < len {
+                    self.segment[byte] &= mask;
+                    byte += p as usize;
+                }
+            }
+        }
+
+        // Clear numbers outside [lo, hi) in the first and last bytes of the range
+        for (i, w) in WHEEL.iter().enumerate() {
+            let first = self.lo / 30;
+            if first >= self.segment_start && u128::from(30 * first) + u128::from(*w) < u128::from(self.lo) {
+                self.segment[(first - self.segment_start) as usize] &= !(1 << i);
+            }
+            let last = self.end_byte - 1;
+            if last < self.segment_start + len as u64 && u128::from(30 * last) + u128::from(*w) >= u128::from(self.hi) {
+                self.segment[(last - self.segment_start) as usize] &= !(1 << i);
+            }
+        }
+
+        true
+    }
+}
+
+impl Iterator for SegmentedSieve {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if let Some(p) = self.wheel_primes.next() {
+            return Some(p);
+        }
+
+        loop {
+            while self.position < self.segment.len() {
+                let bits = self.segment[self.position];
+                if bits != 0 {
+                    // Clear the lowest set bit and return its number
+                    self.segment[self.position] = bits & (bits - 1);
+                    let byte = self.segment_start + self.position as u64;
+                    return Some(30 * byte + WHEEL[bits.trailing_zeros() as usize]);
+                }
+                self.position += 1;
+            }
+
+            if !self._next_segment() {
+                return None;
+            }
+        }
+    }
+}
+
+/// Returns an iterator over the primes in `[lo, hi)`, in increasing order
+///
+/// Before the first prime is returned, every prime up to the square root of `hi` is found and
+/// kept, four bytes each, to sieve the segments with. That is quick and small up to around 2^50,
+/// where it takes about 8MB, but it grows with `hi` however short the range is: for a range just
+/// below `u64::MAX` it is all 203 million primes below 2^32, about 800MB and several seconds of
+/// sieving.
+///
+/// # Arguments
+/// * `lo` - Start of the range
+/// * `hi` - End of the range, which is not included
+pub fn primes_in_range(lo: u64, hi: u64) -> SegmentedSieve {
+    let hi = hi.max(lo);
+    // Every composite below hi has a prime factor of at most isqrt(hi - 1)
+    let limit = _isqrt(hi.saturating_sub(1));
+    let base_primes = if limit <= SIEVE_PRIME_LIMIT as u64 {
+        _sieve_primes().iter().cloned().filter(|p| *p >= 7 && u64::from(*p) <= limit).collect()
+    } else {
+        primes_in_range(7, limit + 1).map(|p| p as u32).collect()
+    };
+
+    SegmentedSieve {
+        lo,
+        hi,
+        wheel_primes: vec![2, 3, 5].into_iter().filter(|p| lo <= *p && *p < hi).collect::<Vec<u64>>().into_iter(),
+        base_primes,
+        segment_start: lo / 30,
+        end_byte: hi / 30 + u64::from(!hi.is_multiple_of(30)),
+        segment: Vec::new(),
+        position: 0,
+    }
 }
 
+/// Returns the number of primes in `[lo, hi)`. Uses the same sieve as `primes_in_range`, counting
+/// the bits of each segment rather than listing the primes.
+pub fn count_primes(lo: u64, hi: u64) -> u64 {
+    let mut sieve = primes_in_range(lo, hi);
+    let mut count = sieve.wheel_primes.len() as u64;
+    while sieve._next_segment() {
+        count += sieve.segment.iter().map(|b| u64::from(b.count_ones())).sum::<u64>();
+    }
+    count
+}
 
-pub fn _test_miller_rabin(num: &BigUint, accuracy: usize, rng: &mut rand::Rng) -> bool {
+
+pub fn _test_miller_rabin<R: RandomSource>(num: &BigUint, accuracy: usize, rng: &mut R) -> bool {
     let two: &BigUint = &BigUint::from_i32(2).expect("Unable to unpack 2");
     let one: BigUint = One::one();
 
-    if num.modpow(&one, two) == Zero::zero() {
+    if *num < BigUint::from_i32(4).unwrap() {
+        return *num >= *two;
+    }
+    if num.is_even() {
         return false;
     }
 
-    let mut odd_factor: BigUint = num - one.clone();
-    let mut pow_two: u64 = 1;
-    while (odd_factor.modpow(&One::one(), two)) == Zero::zero() {
-        odd_factor /= two;
+    let num_minus_one = num - &one;
+    let (odd_factor, pow_two) = _split_pow_two(&num_minus_one);
+    for _ in 0..accuracy {
+        // Base chosen uniformly from [2, num - 2]
+        let a = rng.gen_bigint_range(two, &num_minus_one);
+        if !_strong_probable_prime(num, &a, &odd_factor, pow_two) {
+            return false;
+        }
+    }
+
+    true
+}
+
+
+/// Helper function, writes `num` as `odd_factor * 2^pow_two` and returns `(odd_factor, pow_two)`.
+/// `num` must not be zero.
+pub(crate) fn _split_pow_two(num: &BigUint) -> (BigUint, usize) {
+    let mut odd_factor = num.clone();
+    let mut pow_two = 0;
+    while odd_factor.is_even() {
+        odd_factor >>= 1;
         pow_two += 1;
     }
+    (odd_factor, pow_two)
+}
 
-    let mut log2 = pow_two;
-    let mut odd_copy = odd_factor.clone();
-    while odd_copy > Zero::zero() {
-        log2 += 1;
-        odd_copy /= two;
+/// Helper function, one round of Miller-Rabin: returns whether odd `num` is a strong probable prime
+/// to base `a`, where `num - 1 = odd_factor * 2^pow_two`
+fn _strong_probable_prime(num: &BigUint, a: &BigUint, odd_factor: &BigUint, pow_two: usize) -> bool {
+    let one: BigUint = One::one();
+    let num_minus_one = num - &one;
+
+    let mut x = a.modpow(odd_factor, num);
+    if x == one || x == num_minus_one {
+        return true;
+    }
+
+    for _ in 1..pow_two {
+        x = &x * &x % num;
+        if x == num_minus_one {
+            return true;
+        }
+        if x == one {
+            return false;
+        }
     }
 
-    'outer: for _ in 0..accuracy {
-        let a = rng.next_bigint((log2 / 8) as usize);
-        let mut x = a.modpow(&odd_factor, num);
+    false
+}
+
+/// The first 12 primes, which as bases make Miller-Rabin exact for every number below
+/// 318665857834031151167461 (about 3.18 * 10^23), and so below 2^64 (Sorenson and Webster, 2015)
+const DETERMINISTIC_BASES: [u32; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
 
-        if x == one.clone() || x == (num - one.clone()) {
-            continue 'outer;
+/// Helper function, Miller-Rabin with the fixed `DETERMINISTIC_BASES`, falling back to
+/// Baillie-PSW for numbers of more than 64 bits
+fn _test_miller_rabin_deterministic(num: &BigUint) -> bool {
+    if num.bits() > 64 {
+        return _test_baillie_psw(num);
+    }
+
+    let digits = num.to_u32_digits();
+    if *num < BigUint::from(2_u32) {
+        return false;
+    }
+    for base in DETERMINISTIC_BASES.iter() {
+        if _rem_small(&digits, *base) == 0 {
+            return digits == [*base];
         }
+    }
 
-        for _ in 1..(pow_two - 1) {
-            x = x.modpow(two, num);
-            if x == one {
-                return false;
+    let (odd_factor, pow_two) = _split_pow_two(&(num - 1_u32));
+    DETERMINISTIC_BASES.iter().all(|base| _strong_probable_prime(num, &BigUint::from(*base), &odd_factor, pow_two))
+}
+
+/// Helper function, returns the Jacobi symbol (a/n) for odd `n`, as -1, 0 or 1
+fn _jacobi(a: &BigUint, n: &BigUint) -> i32 {
+    let low_bits = |x: &BigUint| x.to_u32_digits().first().cloned().unwrap_or(0);
+    let mut a = a % n;
+    let mut n = n.clone();
+    let mut result = 1;
+
+    while !a.is_zero() {
+        while a.is_even() {
+            a >>= 1;
+            if low_bits(&n) % 8 == 3 || low_bits(&n) % 8 == 5 {
+                result = -result;
             }
-            if x == (num - one.clone()) {
-                continue 'outer;
+        }
+        std::mem::swap(&mut a, &mut n);
+        if low_bits(&a) % 4 == 3 && low_bits(&n) % 4 == 3 {
+            result = -result;
+        }
+        a %= &n;
+    }
+
+    if n.is_one() { result } else { 0 }
+}
+
+/// Helper function, returns `value mod num` for a possibly negative `value`
+fn _signed_mod(value: i64, num: &BigUint) -> BigUint {
+    let magnitude = BigUint::from(value.unsigned_abs()) % num;
+    if value >= 0 || magnitude.is_zero() { magnitude } else { num - magnitude }
+}
+
+/// Helper function, returns `x / 2 mod num` for odd `num`
+fn _half_mod(x: BigUint, num: &BigUint) -> BigUint {
+    if x.is_odd() { (x + num) >> 1 } else { x >> 1 }
+}
+
+/// Helper function, strong Lucas probable-prime test with Selfridge's parameters: D is the first of
+/// 5, -7, 9, -11, ... with Jacobi symbol (D/n) = -1, P = 1 and Q = (1 - D) / 4
+/// (Baillie and Wagstaff, 1980)
+fn _test_strong_lucas(num: &BigUint) -> bool {
+    let two = BigUint::from(2_u32);
+    if *num <= two {
+        return *num == two;
+    }
+    if num.is_even() {
+        return false;
+    }
+    // There is no suitable D for a square, so catch them before searching
+    let root = num.sqrt();
+    if &root * &root == *num {
+        return false;
+    }
+
+    let mut d: i64 = 5;
+    let d_mod = loop {
+        let d_mod = _signed_mod(d, num);
+        match _jacobi(&d_mod, num) {
+            -1 => break d_mod,
+            // D shares a factor with num, which is only prime if it is that factor
+            0 if BigUint::from(d.unsigned_abs()) != *num => return false,
+            _ => d = if d > 0 { -(d + 2) } else { 2 - d },
+        }
+    };
+    let q = _signed_mod((1 - d) / 4, num);
+
+    // num + 1 = odd_factor * 2^pow_two. Compute U_k, V_k and Q^k for k = odd_factor from the top
+    // bit down, using U_2k = U_k * V_k, V_2k = V_k^2 - 2Q^k and
+    // U_k+1 = (P * U_k + V_k) / 2, V_k+1 = (D * U_k + P * V_k) / 2
+    let (odd_factor, pow_two) = _split_pow_two(&(num + 1_u32));
+    let mut u: BigUint = One::one();
+    let mut v: BigUint = One::one();
+    let mut q_k = q.clone();
+    for i in (0..odd_factor.bits() - 1).rev() {
+        u = &u * &v % num;
+        v = (&v * &v + num - (&q_k << 1) % num) % num;
+        q_k = &q_k * &q_k % num;
+
+        if ((&odd_factor >> i) & BigUint::one()).is_one() {
+            let next_u = _half_mod((&u + &v) % num, num);
+            v = _half_mod((&d_mod * &u + &v) % num, num);
+            u = next_u;
+            q_k = &q_k * &q % num;
+        }
+    }
+
+    // Strong test: U_d = 0, or V_(d * 2^r) = 0 for some 0 <= r < pow_two
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..pow_two {
+        v = (&v * &v + num - (&q_k << 1) % num) % num;
+        if v.is_zero() {
+            return true;
+        }
+        q_k = &q_k * &q_k % num;
+    }
+
+    false
+}
+
+/// Helper function, Baillie-PSW: trial division, a Miller-Rabin round to base 2, then a strong
+/// Lucas test
+fn _test_baillie_psw(num: &BigUint) -> bool {
+    if *num < BigUint::from(2_u32) || !trial_division(num) {
+        return false;
+    }
+    // Every number below SMALL_PRIME_LIMIT^2 without a small factor is prime
+    if num.bits() <= 24 {
+        return true;
+    }
+
+    let (odd_factor, pow_two) = _split_pow_two(&(num - 1_u32));
+    _strong_probable_prime(num, &BigUint::from(2_u32), &odd_factor, pow_two) && _test_strong_lucas(num)
+}
+
+/// A primality test, as used by `is_prime` and `gen_prime`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimalityTest {
+    /// Miller-Rabin with the given number of uniformly random bases. A composite passes each round
+    /// with probability at most 1/4.
+    MillerRabin(usize),
+    /// Miller-Rabin with a fixed set of bases, which is exact below 2^64. Larger numbers are tested
+    /// with Baillie-PSW instead.
+    DeterministicMillerRabin,
+    /// Strong Lucas probable-prime test
+    StrongLucas,
+    /// Baillie-PSW, which combines Miller-Rabin to base 2 with a strong Lucas test. It is exact
+    /// below 2^64 and no composite is known to pass it.
+    BailliePsw,
+}
+
+/// Returns whether `num` is prime according to `test`. Only `PrimalityTest::MillerRabin` uses `rng`.
+///
+/// # Arguments
+/// * `num` - Number to test
+/// * `test` - Primality test to run
+/// * `rng` - Random number generator for the Miller-Rabin bases
+pub fn is_prime<R: RandomSource>(num: &BigUint, test: PrimalityTest, rng: &mut R) -> bool {
+    match test {
+        PrimalityTest::MillerRabin(rounds) => _test_miller_rabin(num, rounds, rng),
+        PrimalityTest::DeterministicMillerRabin => _test_miller_rabin_deterministic(num),
+        PrimalityTest::StrongLucas => _test_strong_lucas(num),
+        PrimalityTest::BailliePsw => _test_baillie_psw(num),
+    }
+}
+
+
+/// Returns the odd primes up to `SMALL_PRIME_LIMIT`, computed once with `sieve_of_eratosthenes`
+fn _small_primes() -> &'static [u32] {
+    static SMALL_PRIMES: OnceLock<Vec<u32>> = OnceLock::new();
+    SMALL_PRIMES.get_or_init(|| sieve_of_eratosthenes(SMALL_PRIME_LIMIT).into_iter().skip(1).collect())
+}
+
+/// Returns the odd primes up to `SIEVE_PRIME_LIMIT`, computed once with `sieve_of_eratosthenes`
+fn _sieve_primes() -> &'static [u32] {
+    static SIEVE_PRIMES: OnceLock<Vec<u32>> = OnceLock::new();
+    SIEVE_PRIMES.get_or_init(|| sieve_of_eratosthenes(SIEVE_PRIME_LIMIT).into_iter().skip(1).collect())
+}
+
+/// Helper function, returns `digits mod m`, where `digits` are the little-endian `u32` digits of a
+/// `BigUint`. Avoids allocating a new `BigUint` for every small prime.
+fn _rem_small(digits: &[u32], m: u32) -> u32 {
+    let m = u64::from(m);
+    digits.iter().rev().fold(0_u64, |r, d| ((r << 32) | u64::from(*d)) % m) as u32
+}
+
+/// Returns false if `num` is divisible by one of the small primes (other than itself), and true if
+/// it has no small factors and may be prime
+pub fn trial_division(num: &BigUint) -> bool {
+    let digits = num.to_u32_digits();
+    if num.is_even() {
+        return digits == [2];
+    }
+
+    _small_primes().iter().all(|p| _rem_small(&digits, *p) != 0 || digits == [*p])
+}
+
+/// Helper function, returns whether `num` is prime to `MILLER_RABIN_ACCURACY` rounds, running the
+/// cheap trial division first
+fn _is_probable_prime<R: RandomSource>(num: &BigUint, rng: &mut R) -> bool {
+    trial_division(num) && _test_miller_rabin(num, MILLER_RABIN_ACCURACY, rng)
+}
+
+/// Helper function, marks every `k` in the sieve window `composite` where start + 2k = `target` mod
+/// `p`, given that start = `residue` mod `p`
+fn _cross_off(composite: &mut [bool], residue: u32, target: u32, p: u32) {
+    let (residue, target, p) = (u64::from(residue), u64::from(target), u64::from(p));
+    // The first such k is (target - residue) / 2 mod p, using 2^-1 = (p + 1) / 2 mod p
+    let mut k = ((target + p - residue) % p * p.div_ceil(2) % p) as usize;
+    while k < composite.len() {
+        composite[k] = true;
+        k += p as usize;
+    }
+}
+
+/// Helper function, searches for a `bits`-bit prime whose top `top_bits` bits are set by sieving
+/// windows of consecutive odd numbers. Each window starts at a random odd number; the small primes
+/// cross off its multiples, and only the survivors get Miller-Rabin tests. If a window has no primes
+/// or would run past `bits` bits, a new start is drawn.
+fn _sieve_search<R: RandomSource>(bits: usize, top_bits: usize, test: PrimalityTest, rng: &mut R) -> BigUint {
+    // A small prime p >= 2^(bits - 1) may be a candidate itself, and divides no other candidate
+    let min_candidate = 1_u64.checked_shl(bits as u32 - 1).unwrap_or(u64::MAX);
+    let sieve_primes: Vec<u32> = _sieve_primes().iter().cloned().filter(|p| u64::from(*p) < min_candidate).collect();
+
+    loop {
+        let start = rng.next_bigint_exact(bits, top_bits, true);
+        let digits = start.to_u32_digits();
+
+        // composite[k] is set when start + 2k has a small factor
+        let mut composite = vec![false; SIEVE_WINDOW];
+        for p in &sieve_primes {
+            _cross_off(&mut composite, _rem_small(&digits, *p), 0, *p);
+        }
+
+        for (k, _) in composite.iter().enumerate().filter(|(_, c)| !**c) {
+            let candidate = &start + BigUint::from(2 * k);
+            if candidate.bits() > bits {
+                break;
+            }
+            if is_prime(&candidate, test, rng) {
+                return candidate;
+            }
+        }
+    }
+}
+
+/// Returns an `n`-bit prime number, drawing fresh random candidates until one is prime
+pub fn gen_large_prime<R: RandomSource>(n: usize, rng: &mut R) -> BigUint {
+    assert!(n >= 2, "Prime must be at least 2 bits long");
+
+    let mut rand_bigint = rng.next_bigint_exact(n, 1, true);
+    while !_is_probable_prime(&rand_bigint, rng) {
+        rand_bigint = rng.next_bigint_exact(n, 1, true);
+    }
+
+    rand_bigint
+}
+
+
+/// Returns an `n`-bit prime number by incremental search from a random odd start, sieving out
+/// candidates with small factors a window at a time. This is much faster than `gen_large_prime`,
+/// at the cost of a slight bias towards primes that follow long runs of composites.
+pub fn gen_large_prime_incremental<R: RandomSource>(n: usize, rng: &mut R) -> BigUint {
+    assert!(n >= 2, "Prime must be at least 2 bits long");
+
+    _sieve_search(n, 1, PrimalityTest::MillerRabin(MILLER_RABIN_ACCURACY), rng)
+}
+
+
+/// Returns a `bits`-bit prime number found by the same incremental search as
+/// `gen_large_prime_incremental`, checking candidates with the given primality test
+pub fn gen_prime<R: RandomSource>(bits: usize, test: PrimalityTest, rng: &mut R) -> BigUint {
+    assert!(bits >= 2, "Prime must be at least 2 bits long");
+
+    _sieve_search(bits, 1, test, rng)
+}
+
+
+/// Returns a `bits`-bit prime number with its top two bits set, so that the product of two such
+/// primes is exactly as long as their lengths added together. Uses the same incremental search as
+/// `gen_large_prime_incremental`.
+pub fn gen_rsa_prime<R: RandomSource>(bits: usize, rng: &mut R) -> BigUint {
+    assert!(bits >= 2, "Prime must be at least 2 bits long");
+
+    _sieve_search(bits, 2, PrimalityTest::MillerRabin(MILLER_RABIN_ACCURACY), rng)
+}
+
+
+/// Returns the number of Miller-Rabin rounds needed for a `bits`-bit RSA prime, from FIPS 186-5
+/// table B.1 for 1024 bits and up, and FIPS 186-4 table C.2 for 512 bits. Smaller primes, which are
+/// only used for teaching, get the same number of rounds as `gen_large_prime`.
+pub fn fips_miller_rabin_rounds(bits: usize) -> usize {
+    match bits {
+        b if b >= 1536 => 4,
+        b if b >= 1024 => 5,
+        b if b >= 512 => 7,
+        _ => MILLER_RABIN_ACCURACY,
+    }
+}
+
+/// Returns a pair of primes `p` and `q` for an `nlen`-bit RSA modulus with public exponent `e`,
+/// following FIPS 186-5 appendix A.1.3:
+///
+/// * both primes have their top two bits set, which puts them above the sqrt(2) * 2^(nlen/2 - 1)
+///   the standard requires, so `p * q` is exactly `nlen` bits long
+/// * `gcd(p - 1, e) = gcd(q - 1, e) = 1`
+/// * `|p - q| > 2^(nlen/2 - 100)`
+/// * both pass the number of Miller-Rabin rounds given by `fips_miller_rabin_rounds`
+///
+/// Returns `None` if `nlen` is odd, `e` is not odd and between 2^16 and 2^256, or, as the standard
+/// requires, if no prime is found after 5 * nlen/2 candidates for `p` or 10 * nlen/2 for `q`. FIPS
+/// 186-5 only allows `nlen` of 2048 and up; smaller sizes are accepted for teaching.
+pub fn gen_fips_rsa_primes<R: RandomSource>(nlen: usize, e: &BigUint, rng: &mut R) -> Option<RsaPrimePair> {
+    let one: BigUint = One::one();
+    if nlen < 4 || !nlen.is_multiple_of(2) || e.is_even() || e.bits() <= 16 || e.bits() > 256 {
+        return None;
+    }
+
+    let half = nlen / 2;
+    let rounds = fips_miller_rabin_rounds(half);
+    let min_distance = &one << half.saturating_sub(100);
+
+    // Checks every condition on a single prime, leaving the primality test until last since it is
+    // by far the slowest
+    let acceptable = |candidate: &BigUint, rng: &mut R| {
+        (candidate - &one).gcd(e) == one
+            && trial_division(candidate)
+            && _test_miller_rabin(candidate, rounds, rng)
+    };
+
+    let mut p_candidates = 0;
+    let p = loop {
+        if p_candidates >= 5 * half {
+            return None;
+        }
+        p_candidates += 1;
+
+        let candidate = rng.next_bigint_exact(half, 2, true);
+        if acceptable(&candidate, rng) {
+            break candidate;
+        }
+    };
+
+    let mut q_candidates = 0;
+    let q = loop {
+        if q_candidates >= 10 * half {
+            return None;
+        }
+        q_candidates += 1;
+
+        let candidate = rng.next_bigint_exact(half, 2, true);
+        let distance = if candidate > p { &candidate - &p } else { &p - &candidate };
+        if distance > min_distance && acceptable(&candidate, rng) {
+            break candidate;
+        }
+    };
+
+    Some(RsaPrimePair { p, q, p_candidates, q_candidates })
+}
+
+
+/// Helper function, returns whether `num` is prime by trial division
+fn _is_prime_u32(num: u32) -> bool {
+    let num = u64::from(num);
+    num >= 2 && (2..).take_while(|d| d * d <= num).all(|d| num % d != 0)
+}
+
+/// Helper function, Pocklington's criterion with a single prime factor: if `factor` is prime,
+/// divides `prime - 1` and is greater than the square root of `prime`, then `prime` is prime when
+/// witness^(prime - 1) = 1 mod prime and gcd(witness^((prime - 1) / factor) - 1, prime) = 1
+fn _pocklington(prime: &BigUint, factor: &BigUint, witness: &BigUint) -> bool {
+    let one: BigUint = One::one();
+    let two = BigUint::from(2_u32);
+    if prime.is_even() || factor * factor <= *prime || *witness < two || *witness >= prime - &one {
+        return false;
+    }
+
+    let prime_minus_one = prime - &one;
+    let (cofactor, remainder) = prime_minus_one.div_rem(factor);
+    if !remainder.is_zero() || witness.modpow(&prime_minus_one, prime) != one {
+        return false;
+    }
+
+    let x = witness.modpow(&cofactor, prime);
+    ((x + &prime_minus_one) % prime).gcd(prime) == one
+}
+
+/// Helper function, Maurer's construction: proves a prime of just over half the size, then searches
+/// for a prime p = 2rq + 1 of `bits` bits, with its top `top_bits` bits set, that Pocklington's
+/// criterion proves from it
+fn _gen_provable_prime<R: RandomSource>(bits: usize, top_bits: usize, rng: &mut R) -> PrimeCertificate {
+    let one: BigUint = One::one();
+    let lowest = ((&one << top_bits) - &one) << (bits - top_bits);
+
+    if bits <= PROVABLE_BASE_BITS {
+        loop {
+            let candidate = rng.next_bigint_exact(bits, top_bits, true);
+            let candidate = candidate.to_u32_digits()[0];
+            if _is_prime_u32(candidate) {
+                return PrimeCertificate { base: candidate, steps: Vec::new() };
             }
         }
+    }
 
+    // q has at least bits/2 + 1 bits, so q^2 > 2^bits > p
+    let mut certificate = _gen_provable_prime(bits.div_ceil(2) + 1, 1, rng);
+    let two_q = certificate.prime() << 1;
+    // Smallest and one past the largest r that give p of the right length
+    let r_min = (&lowest - &one).div_ceil(&two_q);
+    let r_end = ((&one << bits) - &one) / &two_q + &one;
+
+    loop {
+        let r = rng.gen_bigint_range(&r_min, &r_end);
+        let candidate = &two_q * r + &one;
+        if !trial_division(&candidate) {
+            continue;
+        }
+
+        let witness = rng.gen_bigint_range(&BigUint::from(2_u32), &(&candidate - &one));
+        if _pocklington(&candidate, &(&two_q >> 1), &witness) {
+            certificate.steps.push(PocklingtonStep { prime: candidate, witness });
+            return certificate;
+        }
+    }
+}
+
+/// Returns a certificate proving a `bits`-bit prime, which `PrimeCertificate::prime` gives
+pub fn gen_provable_prime<R: RandomSource>(bits: usize, rng: &mut R) -> PrimeCertificate {
+    assert!(bits >= 2, "Prime must be at least 2 bits long");
+
+    _gen_provable_prime(bits, 1, rng)
+}
+
+/// Returns a certificate proving a `bits`-bit prime with its top two bits set, as for
+/// `gen_rsa_prime`
+pub fn gen_provable_rsa_prime<R: RandomSource>(bits: usize, rng: &mut R) -> PrimeCertificate {
+    assert!(bits >= 2, "Prime must be at least 2 bits long");
+
+    _gen_provable_prime(bits, 2, rng)
+}
+
+/// Checks a prime certificate without trusting how it was made: the first prime by trial division,
+/// and every step by Pocklington's criterion from the prime before it
+pub fn verify_certificate(certificate: &PrimeCertificate) -> bool {
+    if !_is_prime_u32(certificate.base) {
         return false;
     }
 
+    let mut factor = BigUint::from(certificate.base);
+    for step in &certificate.steps {
+        if !_pocklington(&step.prime, &factor, &step.witness) {
+            return false;
+        }
+        factor = step.prime.clone();
+    }
+
     true
 }
 
 
-/// Returns an `n`-bit prime number
-pub fn gen_large_prime(n: usize, rng: &mut rand::Rng) -> BigUint {
-    let mut size = n;
-    if n < 8 {
-        size = 8;
+/// Helper function, searches for a `bits`-bit safe prime p = 2q + 1 with its top `top_bits` bits
+/// set. Windows of odd q are sieved so that both q and 2q + 1 are free of small factors, which
+/// leaves few enough candidates for the search to be practical at 1024 bits. Survivors get a
+/// Miller-Rabin round to base 2 on q, then the full test; p then follows from q by Pocklington's
+/// criterion with witness 2.
+fn _gen_safe_prime<R: RandomSource>(bits: usize, top_bits: usize, rng: &mut R) -> BigUint {
+    let one: BigUint = One::one();
+    let two = BigUint::from(2_u32);
+    let q_bits = bits - 1;
+    // Small primes no smaller than q may be q or p themselves
+    let min_q = 1_u64.checked_shl(q_bits as u32 - 1).unwrap_or(u64::MAX);
+    let sieve_primes: Vec<u32> = _sieve_primes().iter().cloned().filter(|p| u64::from(*p) < min_q).collect();
+
+    loop {
+        let start = rng.next_bigint_exact(q_bits, top_bits, true);
+        let digits = start.to_u32_digits();
+
+        // composite[k] is set when q = start + 2k or 2q + 1 has a small factor. 2q + 1 = 0 mod p
+        // exactly when q = (p - 1) / 2 mod p.
+        let mut composite = vec![false; SIEVE_WINDOW];
+        for p in &sieve_primes {
+            let residue = _rem_small(&digits, *p);
+            _cross_off(&mut composite, residue, 0, *p);
+            _cross_off(&mut composite, residue, (p - 1) / 2, *p);
+        }
+
+        for (k, _) in composite.iter().enumerate().filter(|(_, c)| !**c) {
+            let q = &start + BigUint::from(2 * k);
+            if q.bits() > q_bits {
+                break;
+            }
+
+            let (odd_factor, pow_two) = _split_pow_two(&(&q - &one));
+            if !_strong_probable_prime(&q, &two, &odd_factor, pow_two) {
+                continue;
+            }
+            let p = (&q << 1) + &one;
+            if _pocklington(&p, &q, &two) && _test_miller_rabin(&q, MILLER_RABIN_ACCURACY, rng) {
+                return p;
+            }
+        }
+    }
+}
+
+/// Returns a `bits`-bit safe prime p, where (p - 1) / 2 is also prime
+pub fn gen_safe_prime<R: RandomSource>(bits: usize, rng: &mut R) -> BigUint {
+    assert!(bits >= 3, "Safe prime must be at least 3 bits long");
+
+    _gen_safe_prime(bits, 1, rng)
+}
+
+/// Returns a `bits`-bit safe prime with its top two bits set, as for `gen_rsa_prime`. There are none
+/// of 4 or 5 bits, so `bits` must be at least 6.
+pub fn gen_safe_rsa_prime<R: RandomSource>(bits: usize, rng: &mut R) -> BigUint {
+    assert!(bits >= 6, "Safe RSA prime must be at least 6 bits long");
+
+    _gen_safe_prime(bits, 2, rng)
+}
+
+/// Smallest strong prime `gen_strong_prime` will generate, in bits
+pub const MIN_STRONG_PRIME_BITS: usize = 32;
+
+/// Helper function, returns the length of the auxiliary primes for a `bits`-bit strong prime: the
+/// minimums from FIPS 186-5 table A.1 for 1024 bits and up, FIPS 186-4 table B.1 for 512 bits, and
+/// a quarter of the length for smaller primes
+fn _auxiliary_prime_bits(bits: usize) -> usize {
+    match bits {
+        b if b >= 2048 => 201,
+        b if b >= 1536 => 171,
+        b if b >= 1024 => 141,
+        b if b >= 512 => 101,
+        b => b / 4,
     }
+}
+
+/// Helper function, searches for a `bits`-bit strong prime with its top `top_bits` bits set, as in
+/// ANSI X9.31 and FIPS 186-4 appendix C.9. Auxiliary primes r1 and r2 are chosen first, then p is
+/// searched for among the numbers with p = 1 mod 2 * r1 and p = -1 mod r2, so that r1 divides p - 1
+/// and r2 divides p + 1.
+fn _gen_strong_prime<R: RandomSource>(bits: usize, top_bits: usize, rng: &mut R) -> BigUint {
+    let one: BigUint = One::one();
+    let aux_bits = _auxiliary_prime_bits(bits);
+
+    loop {
+        let r1 = _sieve_search(aux_bits, 1, PrimalityTest::MillerRabin(MILLER_RABIN_ACCURACY), rng);
+        let r2 = _sieve_search(aux_bits, 1, PrimalityTest::MillerRabin(MILLER_RABIN_ACCURACY), rng);
+        let two_r1 = &r1 << 1;
+        if two_r1.gcd(&r2) != one {
+            continue;
+        }
+
+        // By the Chinese remainder theorem, start = 1 mod 2 * r1 and start = -1 mod r2
+        let step = &two_r1 * &r2;
+        let start = (rsa::_modular_multiplicative_inverse(r2.clone(), two_r1.clone()) * &r2 + &step
+            - rsa::_modular_multiplicative_inverse(two_r1.clone(), r2.clone()) * &two_r1 % &step) % &step;
 
-    let mut rand_bigint = rng.next_bigint(size / 8);
-    while !_test_miller_rabin(&rand_bigint, MILLER_RABIN_ACCURACY, rng) {
-        rand_bigint = rng.next_bigint(size / 8);
+        // Step from a random x up to the first candidate in the right residue class, and on until
+        // the candidates would be too long
+        let x = rng.next_bigint_exact(bits, top_bits, false);
+        let mut candidate = &x + (&start + &step - &x % &step) % &step;
+        while candidate.bits() <= bits {
+            if _is_probable_prime(&candidate, rng) {
+                return candidate;
+            }
+            candidate += &step;
+        }
     }
+}
 
-    rand_bigint
+/// Returns a `bits`-bit strong prime p, where p - 1 and p + 1 each have a large prime factor. `bits`
+/// must be at least `MIN_STRONG_PRIME_BITS`.
+pub fn gen_strong_prime<R: RandomSource>(bits: usize, rng: &mut R) -> BigUint {
+    assert!(bits >= MIN_STRONG_PRIME_BITS, "Strong prime must be at least {} bits long", MIN_STRONG_PRIME_BITS);
+
+    _gen_strong_prime(bits, 1, rng)
+}
+
+/// Returns a `bits`-bit strong prime with its top two bits set, as for `gen_rsa_prime`
+pub fn gen_strong_rsa_prime<R: RandomSource>(bits: usize, rng: &mut R) -> BigUint {
+    assert!(bits >= MIN_STRONG_PRIME_BITS, "Strong prime must be at least {} bits long", MIN_STRONG_PRIME_BITS);
+
+    _gen_strong_prime(bits, 2, rng)
 }
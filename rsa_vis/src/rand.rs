@@ -1,5 +1,10 @@
+use std::cell::RefCell;
 use std::time::{SystemTime, UNIX_EPOCH};
+use std::fs::File;
+use std::io::Read;
 use num::BigUint;
+use num::traits::One;
+use crate::chacha;
 
 /// The modulus constant from the GCC implementation of rand (2^31). We can make this more efficient
 /// with binary trickery if we so choose, since it's just a power of two.
@@ -9,6 +14,109 @@ const GCC_MULT: u64 = 1103515245;
 /// The increment constant from GCC
 const GCC_INC: u64 = 12345;
 
+/// Source of the seed for `ChaChaRng::from_os`
+const OS_RANDOM_PATH: &str = "/dev/urandom";
+
+thread_local! {
+    /// Generator for code that needs random numbers but isn't given a generator, such as blinding in
+    /// `rsa::rsadp`. Seeded from the operating system when first used in each thread.
+    static THREAD_RNG: RefCell<ChaChaRng> = RefCell::new(new_secure());
+}
+
+/// A source of random numbers, implemented by every generator in this module. Everything that uses
+/// random numbers is generic over this, so generators can be swapped or compared side by side, and
+/// seeded generators can be passed in for reproducible runs. Code that makes key material or
+/// padding should be given a `ChaChaRng`.
+pub trait RandomSource {
+    /// Return the next 32 random bits
+    fn next_u32(&mut self) -> u32;
+
+    /// Return the next 64 random bits
+    fn next_u64(&mut self) -> u64 {
+        let high = self.next_u32() as u64;
+        let low = self.next_u32() as u64;
+
+        (high << 32) | low
+    }
+
+    /// Return a random number in the range [0..1)
+    fn next_f64(&mut self) -> f64 {
+        // 53 random bits fill the mantissa of an f64 exactly
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Return a random integer in the range [min..max), with every value equally likely
+    fn gen_range(&mut self, min: u64, max: u64) -> u64 {
+        assert!(min < max, "Range must not be empty");
+        let range = max - min;
+
+        // Reject values from the incomplete copy of the range at the top of the u64s, which would
+        // otherwise make the smallest values slightly more likely
+        let excess = (u64::MAX % range + 1) % range;
+        loop {
+            let value = self.next_u64();
+            if value <= u64::MAX - excess {
+                return min + value % range;
+            }
+        }
+    }
+
+    /// Fill `dest` with random bytes
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_be_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    /// Return a random BigUint made up of `num_bytes` random bytes
+    fn next_bigint(&mut self, num_bytes: usize) -> BigUint {
+        let mut bytes = vec![0_u8; num_bytes];
+        self.fill_bytes(&mut bytes);
+
+        BigUint::from_bytes_be(&bytes)
+    }
+
+    /// Return a random BigUint in the range [0..2^bits)
+    fn next_bigint_bits(&mut self, bits: usize) -> BigUint {
+        let num_bytes = bits.div_ceil(8);
+
+        self.next_bigint(num_bytes) >> (num_bytes * 8 - bits)
+    }
+
+    /// Return a random BigUint exactly `bits` bits long, chosen uniformly from the numbers whose
+    /// `top_bits` most significant bits are all set, and which are odd if `odd` is true. Setting two
+    /// top bits is useful for RSA primes, since the product of two such numbers has full length.
+    fn next_bigint_exact(&mut self, bits: usize, top_bits: usize, odd: bool) -> BigUint {
+        assert!(top_bits >= 1 && top_bits <= bits, "Must force between 1 and `bits` top bits");
+        let one: BigUint = One::one();
+        let top_mask = ((&one << top_bits) - &one) << (bits - top_bits);
+
+        let res = self.next_bigint_bits(bits) | top_mask;
+        if odd {
+            res | one
+        } else {
+            res
+        }
+    }
+
+    /// Return a random BigUint in the range [lo..hi), with every value equally likely
+    fn gen_bigint_range(&mut self, lo: &BigUint, hi: &BigUint) -> BigUint {
+        assert!(lo < hi, "Range must not be empty");
+        let range = hi - lo;
+        let bits = range.bits();
+
+        // Draw numbers as long as the range until one lands inside it. Fewer than half are
+        // rejected, since the range is at least half of 2^bits.
+        loop {
+            let value = self.next_bigint_bits(bits);
+            if value < range {
+                return lo + value;
+            }
+        }
+    }
+}
+
 /// This struct represents a random number generator using the linear congruential method (L.C.M.),
 /// since RNG is ultimately a sequence. `Rng` is not accessible outside of the `rand` module,
 /// creation will have two layers of abstraction.
@@ -27,10 +135,10 @@ impl Rng {
     /// Returns a new random number generator with the parameters specified here
     ///
     /// # Arguments
-    ///     * `modulus` - The modulus value for L.C.M.
-    ///     * `multiplier` - The multiplier for L.C.M.
-    ///     * `increment` - The increment for L.C.M.
-    ///     * `seed` - Initial value for L.C.M. sequence
+    /// * `modulus` - The modulus value for L.C.M.
+    /// * `multiplier` - The multiplier for L.C.M.
+    /// * `increment` - The increment for L.C.M.
+    /// * `seed` - Initial value for L.C.M. sequence
     fn new(modulus: u64, multiplier: u64, increment: u64, seed: u64) -> Rng {
         let mut ret = Rng {
             a: multiplier,
@@ -44,26 +152,85 @@ impl Rng {
     }
 
     /// Return the next random number in the sequence, normalized as a value in the range [0..1)
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> f64 {
         self.x = (self.a * self.x + self.c) % self.m;
         (self.x as f64) / (self.m as f64)
     }
 
-    /// Return the next random number in the sequence, as an integer in the range [min..max)
+    /// Return the next random number in the sequence, as an integer in the range [min..max). Every
+    /// value is equally likely; see `RandomSource::gen_range`.
     pub fn next_int(&mut self, min: u64, max: u64) -> u64 {
-        let range: f64 = (max - min) as f64;
+        self.gen_range(min, max)
+    }
+
+}
 
-        min + ((self.next() * range) as u64)
+impl RandomSource for Rng {
+    /// Return the next 32 bits from the sequence. Only the top 16 bits of each L.C.M. value are used,
+    /// since the low bits of an L.C.M. with a power of two modulus repeat with a short period.
+    fn next_u32(&mut self) -> u32 {
+        let high = (self.next() * 65536.0) as u32;
+        let low = (self.next() * 65536.0) as u32;
+
+        (high << 16) | low
     }
 
-    /// Return the next random number in the sequence, normalized as a BigUint of size num_bytes
-    pub fn next_bigint(&mut self, num_bytes: usize) -> BigUint {
-        let mut bytes = vec![0_u8; num_bytes];
-        for i in 0..num_bytes {
-            bytes[i] = (self.next() * (u8::MAX as f64)) as u8;
+    /// Return the next L.C.M. value directly, so that statistics run on an `Rng` measure the
+    /// generator itself
+    fn next_f64(&mut self) -> f64 {
+        self.next()
+    }
+}
+
+/// A cryptographically secure random number generator, producing the ChaCha20 keystream for a
+/// secret 256-bit seed. Use this rather than `Rng` for anything that has to stay secret.
+pub struct ChaChaRng {
+    /// ChaCha20 key, i.e. the seed
+    key: [u32; 8],
+    /// Index of the next keystream block
+    counter: u64,
+    /// Current keystream block
+    block: [u32; 16],
+    /// Index of the next unused word in `block`
+    index: usize,
+}
+
+impl ChaChaRng {
+    /// Returns a new generator for the given 256-bit seed. The same seed always gives the same output.
+    pub fn from_seed(seed: [u8; 32]) -> ChaChaRng {
+        let mut key = [0_u32; 8];
+        chacha::le_words(&seed, &mut key);
+
+        ChaChaRng { key, counter: 0, block: [0; 16], index: 16 }
+    }
+
+    /// Returns a new generator seeded from the operating system's random number generator
+    pub fn from_os() -> std::io::Result<ChaChaRng> {
+        let mut seed = [0_u8; 32];
+        File::open(OS_RANDOM_PATH)?.read_exact(&mut seed)?;
+
+        Ok(ChaChaRng::from_seed(seed))
+    }
+
+    /// Generate the next keystream block. The 64-bit block counter is split across the counter and
+    /// first nonce word, so the stream won't repeat within any realistic amount of output.
+    fn refill(&mut self) {
+        let nonce = [(self.counter >> 32) as u32, 0, 0];
+        self.block = chacha::chacha20_block(&self.key, self.counter as u32, &nonce);
+        self.counter += 1;
+        self.index = 0;
+    }
+}
+
+impl RandomSource for ChaChaRng {
+    fn next_u32(&mut self) -> u32 {
+        if self.index == self.block.len() {
+            self.refill();
         }
+        self.index += 1;
 
-        BigUint::from_bytes_be(&bytes)
+        self.block[self.index - 1]
     }
 }
 
@@ -80,3 +247,14 @@ pub fn new() -> Rng {
 
     new_seed(seed)
 }
+
+/// Returns a new cryptographically secure RNG object, seeded from the operating system
+pub fn new_secure() -> ChaChaRng {
+    ChaChaRng::from_os().expect("Unable to read random seed from the operating system")
+}
+
+/// Calls `f` with this thread's `ChaChaRng`, which is seeded from the operating system when first
+/// used. For code that needs secure random numbers but has no generator passed in.
+pub fn with_thread_rng<T, F: FnOnce(&mut ChaChaRng) -> T>(f: F) -> T {
+    THREAD_RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
@@ -1,28 +1,375 @@
-use num::{BigUint, BigInt, ToPrimitive, FromPrimitive};
+use num::{BigUint, BigInt, FromPrimitive};
 use num::traits::{One, Zero};
+use num::Integer;
+use num::bigint::ToBigInt;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use crate::rand;
+use crate::rand::RandomSource;
 use crate::primes;
-use crate::num::bigint::ToBigInt;
+use crate::primes::PrimeCertificate;
+use crate::pkcs1;
+use crate::hash::HashAlgorithm;
 
 const KEY_SIZE: usize = 1024;
-const BLOCK_SIZE: usize = 4; // Block size in increments of 8 bytes
+const DEFAULT_EXPONENT: u32 = 65_537;
+/// Smallest modulus accepted by `KeyBuilder`, in bits. Far too small to be secure, but large enough
+/// that there are always two distinct primes to pick from.
+pub const MIN_KEY_SIZE: usize = 16;
+/// Length in bytes of the message length that `encrypt` puts before the message
+const LENGTH_PREFIX_SIZE: usize = 8;
+/// Random bases tried by `recover_primes` before giving up. Each one finds the primes with
+/// probability at least 1/2, so a genuine key only fails with probability 2^-100.
+const PRIME_RECOVERY_ATTEMPTS: usize = 100;
+/// Bits in the random multiplier k that exponent blinding adds k times a multiple of lambda(n) with
+const EXPONENT_BLINDING_BITS: u32 = 64;
+
+/// An RSA public key, made up of the modulus `n` and the public exponent `e`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RsaPublicKey {
+    /// Modulus, the product of the two secret primes
+    pub n: BigUint,
+    /// Public exponent
+    pub e: BigUint,
+}
+
+/// An RSA private key. Carries the public components alongside the private exponent `d`, so that a
+/// private key is all that is needed to both encrypt and decrypt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RsaPrivateKey {
+    /// Modulus, the product of `p` and `q`
+    pub n: BigUint,
+    /// Public exponent
+    pub e: BigUint,
+    /// Private exponent, the modular multiplicative inverse of `e` mod lambda(n)
+    pub d: BigUint,
+    /// First prime factor of `n`. Not present for keys saved without their primes.
+    pub p: Option<BigUint>,
+    /// Second prime factor of `n`. Not present for keys saved without their primes.
+    pub q: Option<BigUint>,
+    /// Values used to speed up private key operations with the Chinese Remainder Theorem. Only
+    /// present when `p` and `q` are known.
+    pub crt: Option<CrtParams>,
+    /// Countermeasures against timing attacks applied by `rsadp`. Base blinding by default.
+    pub blinding: Blinding,
+}
+
+/// Countermeasures `rsadp` applies so that the time a private key operation takes, which depends on
+/// the numbers exponentiated, reveals nothing useful about the private key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Blinding {
+    /// Exponentiate the cipher as it is. Only for teaching and visualisation, where the numbers
+    /// should be the ones on the page and should repeat from run to run.
+    None,
+    /// Multiply the cipher by r^e for a random r before exponentiating, and the result by r^-1
+    /// after, so the number exponentiated is unrelated to the one an attacker chose
+    #[default]
+    Base,
+    /// Base blinding, plus adding a random multiple of lambda(n) (or of p - 1 and q - 1 with the CRT)
+    /// to the exponent, so the exponent used changes every time too
+    BaseAndExponent,
+}
+
+/// Chinese Remainder Theorem values for a private key (RFC 8017 section 3.2)
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrtParams {
+    /// `d mod (p - 1)`
+    pub dp: BigUint,
+    /// `d mod (q - 1)`
+    pub dq: BigUint,
+    /// Inverse of `q` mod `p`
+    pub qinv: BigUint,
+}
+
+impl CrtParams {
+    /// Computes the CRT values for private exponent `d` and primes `p` and `q`
+    pub fn new(d: &BigUint, p: &BigUint, q: &BigUint) -> CrtParams {
+        let one: BigUint = One::one();
+        CrtParams {
+            dp: d % (p - &one),
+            dq: d % (q - &one),
+            qinv: _modular_multiplicative_inverse(q % p, p.clone()),
+        }
+    }
+}
+
+/// Errors returned by the padded encryption and decryption functions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The message does not fit in a single block for the modulus of the key
+    MessageTooLong,
+    /// The cipher could not be decrypted. Deliberately carries no detail about why, so that callers
+    /// can't be turned into a padding oracle.
+    Decryption,
+    /// The modulus is too small for the requested operation, e.g. for the hash or salt length
+    KeyTooSmall,
+    /// The key size or public exponent given for key generation can't be used
+    InvalidParameters,
+    /// The signature is malformed or does not match the message
+    Verification,
+    /// The key's components don't belong together, e.g. `d` is not the inverse of `e`
+    InvalidKey,
+    /// The key is not one of the recipients a message was encrypted for
+    NotRecipient,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MessageTooLong => write!(f, "message too long for key"),
+            Error::Decryption => write!(f, "decryption error"),
+            Error::KeyTooSmall => write!(f, "key too small for the chosen parameters"),
+            Error::InvalidParameters => write!(f, "invalid key size or exponent"),
+            Error::Verification => write!(f, "invalid signature"),
+            Error::InvalidKey => write!(f, "inconsistent key components"),
+            Error::NotRecipient => write!(f, "key is not among the recipients"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl RsaPublicKey {
+    /// Returns a new public key from modulus `n` and exponent `e`
+    pub fn new(n: BigUint, e: BigUint) -> RsaPublicKey {
+        RsaPublicKey { n, e }
+    }
+
+    /// Returns the length of the modulus in bytes
+    pub fn size(&self) -> usize {
+        self.n.bits().div_ceil(8)
+    }
+}
+
+impl RsaPrivateKey {
+    /// Returns a new private key from its components. `p` and `q` may be omitted when the primes are
+    /// not known, e.g. for keys read from older key files. When they are given, the CRT values are
+    /// computed from them. The key uses the default `Blinding`.
+    pub fn new(n: BigUint, e: BigUint, d: BigUint, p: Option<BigUint>, q: Option<BigUint>) -> RsaPrivateKey {
+        let crt = match (&p, &q) {
+            (Some(p), Some(q)) => Some(CrtParams::new(&d, p, q)),
+            _ => None,
+        };
+        RsaPrivateKey { n, e, d, p, q, crt, blinding: Blinding::default() }
+    }
+
+    /// Returns this key set to use `blinding` for private key operations
+    pub fn with_blinding(mut self, blinding: Blinding) -> RsaPrivateKey {
+        self.blinding = blinding;
+        self
+    }
+
+    /// Returns the public half of this key
+    pub fn to_public_key(&self) -> RsaPublicKey {
+        RsaPublicKey::new(self.n.clone(), self.e.clone())
+    }
+
+    /// Returns the length of the modulus in bytes
+    pub fn size(&self) -> usize {
+        self.n.bits().div_ceil(8)
+    }
+
+    /// Returns this key with its primes and CRT values filled in, recovering the primes from `n`, `e`
+    /// and `d` with `recover_primes` if the key doesn't have them. Upgrades keys saved without their
+    /// primes, which are slower to use and can't be exported to formats that need them. Fails with
+    /// `Error::InvalidKey` if the primes can't be found, which means `d` doesn't match `e`.
+    ///
+    /// # Arguments
+    /// * `rng` - Random number generator for choosing bases
+    pub fn with_primes<R: RandomSource>(&self, rng: &mut R) -> Result<RsaPrivateKey, Error> {
+        if self.p.is_some() && self.q.is_some() {
+            return Ok(self.clone());
+        }
+
+        let (p, q) = recover_primes(&self.n, &self.e, &self.d, rng).ok_or(Error::InvalidKey)?;
+        Ok(RsaPrivateKey::new(self.n.clone(), self.e.clone(), self.d.clone(), Some(p), Some(q))
+            .with_blinding(self.blinding))
+    }
+}
+
+/// Helper function, squares g^r mod `n` up to `t` times and returns the last value before reaching 1,
+/// if that is a square root of 1 other than 1 and -1 (NIST SP 800-56B appendix C.2, steps 3b to 3f)
+fn _square_root_of_one(g: &BigUint, r: &BigUint, t: usize, n: &BigUint) -> Option<BigUint> {
+    let one: BigUint = One::one();
+    let n_minus_one = n - &one;
+
+    let mut y = g.modpow(r, n);
+    if y == one || y == n_minus_one {
+        return None;
+    }
+    for _ in 0..t {
+        let x = &y * &y % n;
+        if x == one {
+            return Some(y);
+        }
+        if x == n_minus_one {
+            return None;
+        }
+        y = x;
+    }
+    None
+}
+
+/// Returns the prime factors p > q of modulus `n`, given public exponent `e` and private exponent
+/// `d`, by the probabilistic method of NIST SP 800-56B appendix C.2. Since ed - 1 = 2^t r is a
+/// multiple of lambda(n), g^r for random g can be squared at most t times before reaching 1 mod `n`,
+/// and the last value y before 1 is a square root of 1. Half the time y is neither 1 nor -1, and then
+/// gcd(y - 1, n) is a prime factor. Returns `None` if no factor is found, which for a genuine key
+/// happens with negligible probability.
+///
+/// # Arguments
+/// * `n` - Modulus, a product of two distinct primes
+/// * `e` - Public exponent
+/// * `d` - Private exponent matching `e`, the inverse of `e` mod either lambda(n) or phi(n)
+/// * `rng` - Random number generator for choosing bases
+pub fn recover_primes<R: RandomSource>(n: &BigUint, e: &BigUint, d: &BigUint,
+                                       rng: &mut R) -> Option<(BigUint, BigUint)> {
+    let one: BigUint = One::one();
+    let two = BigUint::from_u32(2).unwrap();
+    if d.is_zero() || e.is_zero() || *n <= BigUint::from_u32(3).unwrap() {
+        return None;
+    }
+
+    // 1. k = de - 1, which must be even for d to be an inverse of odd e
+    let k = d * e - &one;
+    if k.is_zero() || k.is_odd() {
+        return None;
+    }
+
+    // 2. Write k = 2^t r with r odd
+    let (r, t) = primes::_split_pow_two(&k);
+    let n_minus_one = n - &one;
+
+    // 3. Look for a non-trivial square root of 1 from random bases
+    for _ in 0..PRIME_RECOVERY_ATTEMPTS {
+        let g = rng.gen_bigint_range(&two, &n_minus_one);
+        let shared = g.gcd(n);
+        let p = if !shared.is_one() {
+            shared
+        } else {
+            // 5. gcd(y - 1, n) splits n for a square root y of 1 other than 1 and -1
+            match _square_root_of_one(&g, &r, t, n) {
+                Some(y) => (y - &one).gcd(n),
+                None => continue,
+            }
+        };
+        let q = n / &p;
+        return Some(if p > q { (p, q) } else { (q, p) });
+    }
+
+    None
+}
+
+/// Converts `x` to a big-endian byte string of exactly `len` bytes (I2OSP, RFC 8017 section 4.1).
+/// Returns `None` if `x` is too large to fit in `len` bytes.
+pub fn i2osp(x: &BigUint, len: usize) -> Option<Vec<u8>> {
+    if x.is_zero() {
+        return Some(vec![0_u8; len]);
+    }
+    let bytes = x.to_bytes_be();
+    if bytes.len() > len {
+        return None;
+    }
+
+    let mut res = vec![0_u8; len - bytes.len()];
+    res.extend_from_slice(&bytes);
+    Some(res)
+}
+
+/// Converts a big-endian byte string to an integer (OS2IP, RFC 8017 section 4.2)
+pub fn os2ip(bytes: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(bytes)
+}
+
+/// Raises message representative `m` to the public exponent (RSAEP, RFC 8017 section 5.1.1)
+pub fn rsaep(m: &BigUint, key: &RsaPublicKey) -> BigUint {
+    m.modpow(&key.e, &key.n)
+}
+
+/// Raises cipher representative `c` to the private exponent (RSADP, RFC 8017 section 5.1.2), with
+/// the key's `blinding`. Every private key operation, decryption or signing, goes through here.
+/// Random numbers for blinding come from `rand::with_thread_rng`; see `rsadp_with_rng`.
+pub fn rsadp(c: &BigUint, key: &RsaPrivateKey) -> BigUint {
+    match key.blinding {
+        Blinding::None => _rsadp(c, key, &Zero::zero()),
+        _ => rand::with_thread_rng(|rng| rsadp_with_rng(c, key, rng)),
+    }
+}
+
+/// `rsadp` with random numbers for blinding taken from `rng`
+///
+/// # Arguments
+/// * `c` - Cipher representative, less than the modulus
+/// * `key` - Private key, whose `blinding` says what to do with `rng`
+/// * `rng` - Random number generator for the blinding factors, which should be a `ChaChaRng`
+pub fn rsadp_with_rng<R: RandomSource>(c: &BigUint, key: &RsaPrivateKey, rng: &mut R) -> BigUint {
+    let exponent_factor = match key.blinding {
+        Blinding::None => return _rsadp(c, key, &Zero::zero()),
+        Blinding::Base => Zero::zero(),
+        Blinding::BaseAndExponent => BigUint::from(rng.next_u64() >> (64 - EXPONENT_BLINDING_BITS)),
+    };
+
+    // (c r^e)^d = c^d r mod n, so multiplying by r^-1 afterwards leaves c^d. r must be invertible;
+    // finding one that isn't would mean having found a factor of n.
+    let one: BigUint = One::one();
+    let r = loop {
+        let r = rng.gen_bigint_range(&one, &key.n);
+        if r.gcd(&key.n).is_one() {
+            break r;
+        }
+    };
+    let r_inverse = _modular_multiplicative_inverse(r.clone(), key.n.clone());
+    let blinded = c * rsaep(&r, &key.to_public_key()) % &key.n;
+    _rsadp(&blinded, key, &exponent_factor) * r_inverse % &key.n
+}
+
+/// Helper function, raises `c` to the private exponent plus `exponent_factor` times a multiple of
+/// the group order, which gives the same result. Uses the CRT values when the key has them, and a
+/// single exponentiation mod `n` otherwise.
+fn _rsadp(c: &BigUint, key: &RsaPrivateKey, exponent_factor: &BigUint) -> BigUint {
+    let one: BigUint = One::one();
+    match (&key.p, &key.q, &key.crt) {
+        (Some(p), Some(q), Some(crt)) if exponent_factor.is_zero() => _rsadp_crt(c, p, q, crt),
+        (Some(p), Some(q), Some(crt)) => {
+            let blinded = CrtParams {
+                dp: &crt.dp + exponent_factor * (p - &one),
+                dq: &crt.dq + exponent_factor * (q - &one),
+                qinv: crt.qinv.clone(),
+            };
+            _rsadp_crt(c, p, q, &blinded)
+        },
+        // ed - 1 is a multiple of lambda(n), and is known even when the primes aren't
+        _ => c.modpow(&(&key.d + exponent_factor * (&key.e * &key.d - &one)), &key.n),
+    }
+}
+
+/// Helper function, computes `c^d mod pq` from two half-size exponentiations, recombined with
+/// Garner's formula
+fn _rsadp_crt(c: &BigUint, p: &BigUint, q: &BigUint, crt: &CrtParams) -> BigUint {
+    let m_1 = (c % p).modpow(&crt.dp, p);
+    let m_2 = (c % q).modpow(&crt.dq, q);
+
+    // h = qInv * (m_1 - m_2) mod p, adding p first so the subtraction can't go negative
+    let h = (&crt.qinv * ((m_1 + p) - (&m_2 % p))) % p;
+    m_2 + h * q
+}
 
 /// Return greatest common divisor of elements a and b as a BigUint
 fn _gcd(a: BigUint, b: BigUint) -> BigUint {
-    return if b == Zero::zero() {
-        a.clone()
+    if b == Zero::zero() {
+        a
     } else {
-        _gcd(b.clone(), a % b.clone())
+        _gcd(b.clone(), a % b)
     }
 }
 
 /// Return modular multiplicative inverse of a and m as a BigUint
-fn _modular_multiplicative_inverse(mut a: BigUint, mut m: BigUint) -> BigUint {
+pub(crate) fn _modular_multiplicative_inverse(a: BigUint, m: BigUint) -> BigUint {
     // This code adapted from GeeksForGeeks: https://www.geeksforgeeks.org/multiplicative-inverse-under-modulo-m/
     let mut a = a.to_bigint().unwrap();
     let mut m = m.to_bigint().unwrap();
 
-    let mut m0 = m.clone();
+    let m0 = m.clone();
     let mut y: BigInt = BigInt::zero();
     let mut x: BigInt = BigInt::one();
 
@@ -31,7 +378,7 @@ fn _modular_multiplicative_inverse(mut a: BigUint, mut m: BigUint) -> BigUint {
     }
 
     while a > BigInt::one() {
-        let mut q: BigInt = a.clone() / m.clone();
+        let q: BigInt = a.clone() / m.clone();
         let mut t: BigInt = m.clone();
 
         // m is remainder now, process same as Euclid's algo
@@ -44,170 +391,400 @@ fn _modular_multiplicative_inverse(mut a: BigUint, mut m: BigUint) -> BigUint {
     }
     // Make x positive if needed
     if x < BigInt::zero() {
-        x = x + m0;
+        x += m0;
     }
 
-    return x.to_biguint().unwrap();
+    x.to_biguint().unwrap()
 }
 
-fn _gen_key(num_prime_bits: usize) -> ((BigUint, BigUint), BigUint) {
+/// Helper function, generates a key with an `bits`-bit modulus and public exponent `exponent`,
+/// along with certificates for its primes when `method` is `PrimeMethod::Provable`. Draws new
+/// primes until they satisfy every condition, rather than giving up on a bad pair.
+fn _gen_key<R: RandomSource>(bits: usize, exponent: &BigUint, method: PrimeMethod,
+                             rng: &mut R) -> (RsaPrivateKey, Vec<PrimeCertificate>) {
     // Algorithm adapted from https://en.wikipedia.org/wiki/RSA_(cryptosystem)#Key_generation
-    let mut rng = rand::new();
     let one: BigUint = One::one();
+    let prime_one_bits = bits.div_ceil(2);
+    let prime_two_bits = bits - prime_one_bits;
+
+    loop {
+        // 1. Choose distinct prime numbers prime_one and prime_two, large enough that n always has
+        // exactly `bits` bits.
+        let mut certificates = Vec::new();
+        let (prime_one, prime_two) = match method {
+            PrimeMethod::Random => (primes::gen_rsa_prime(prime_one_bits, rng),
+                                    primes::gen_rsa_prime(prime_two_bits, rng)),
+            PrimeMethod::Fips186 => match primes::gen_fips_rsa_primes(bits, exponent, rng) {
+                Some(pair) => (pair.p, pair.q),
+                None => continue,
+            },
+            PrimeMethod::Safe => (primes::gen_safe_rsa_prime(prime_one_bits, rng),
+                                  primes::gen_safe_rsa_prime(prime_two_bits, rng)),
+            PrimeMethod::Strong => (primes::gen_strong_rsa_prime(prime_one_bits, rng),
+                                    primes::gen_strong_rsa_prime(prime_two_bits, rng)),
+            PrimeMethod::Provable => {
+                certificates.push(primes::gen_provable_rsa_prime(prime_one_bits, rng));
+                certificates.push(primes::gen_provable_rsa_prime(prime_two_bits, rng));
+                (certificates[0].prime(), certificates[1].prime())
+            },
+        };
+        if prime_one == prime_two {
+            continue;
+        }
+
+        // 2. Compute n = prime_one * prime_two
+        // n is used as the modulus for both the public and private keys.
+        let n = &prime_one * &prime_two;
+        if n.bits() != bits {
+            continue;
+        }
+
+        // 3. Compute lambda_n = lcm(p-1, q-1). Note that lcm(a, b) = abs(a*b} / gcd(a, b).
+        // Here, prime_one > 0 and prime_two > 0, so prime_one*prime_two = abs(prime_one*prime_two)
+        let prod: BigUint = (prime_one.clone() - one.clone()) * (prime_two.clone() - one.clone());
+        let lambda_n = prod / _gcd(prime_one.clone() - one.clone(),
+                            prime_two.clone() - one.clone());
 
-    let mut prime_rng = rand::new();
-    // 1. Choose distinct prime numbers prime_one and prime_two
-    let prime_one = primes::gen_large_prime(num_prime_bits, &mut prime_rng);
-    let mut prime_two = primes::gen_large_prime(num_prime_bits, &mut prime_rng);
-    while prime_one == prime_two {
-        prime_two = primes::gen_large_prime(num_prime_bits, &mut prime_rng);
+        // 4. e must satisfy 1 < e < lambda_n, and e and lambda_n must be co-prime. If not, try
+        // again with different primes.
+        if *exponent >= lambda_n || _gcd(exponent.clone(), lambda_n.clone()) != one {
+            continue;
+        }
+
+        // 5. Compute d s.t. d * e ≡ 1 mod lambda_n. d is modular multiplicative inverse of e, lambda_n
+        // d is the private key exponent
+        let d: BigUint = _modular_multiplicative_inverse(exponent.clone(), lambda_n.clone());
+
+        return (RsaPrivateKey::new(n, exponent.clone(), d, Some(prime_one), Some(prime_two)), certificates);
     }
+}
 
-    // 2. Compute n = prime_one * prime_two (only needed as return val, computed below)
-    // n is used as the modulus for both the public and private keys.
+/// How `KeyBuilder` chooses the primes of a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimeMethod {
+    /// Independent random primes with their top two bits set
+    Random,
+    /// Probable primes generated as in FIPS 186-5 appendix A.1.3. Needs an even key size and an
+    /// exponent between 2^16 and 2^256.
+    Fips186,
+    /// Primes constructed along with certificates that prove them prime, see
+    /// `primes::gen_provable_prime`
+    Provable,
+    /// Safe primes p = 2q + 1 with q prime, see `primes::gen_safe_prime`. Slow for large keys, and
+    /// needs a key of at least twice `primes::MIN_STRONG_PRIME_BITS` so that there are enough of them
+    /// to choose from.
+    Safe,
+    /// Strong primes in the sense of ANSI X9.31, see `primes::gen_strong_prime`. Needs a key of at
+    /// least twice `primes::MIN_STRONG_PRIME_BITS`.
+    Strong,
+}
 
-    // 3. Compute lambda_n = lcm(p-1, q-1). Note that lcm(a, b) = abs(a*b} / gcd(a, b).
-    // Here, prime_one > 0 and prime_two > 0, so prime_one*prime_two = abs(prime_one*prime_two)
-    let prod: BigUint = (prime_one.clone() - one.clone()) * (prime_two.clone() - one.clone());
-    let lambda_n = prod / _gcd(prime_one.clone() - one.clone(),
-                        prime_two.clone() - one.clone());
+/// Builder for generating RSA keys with a chosen modulus size and public exponent
+#[derive(Clone, Debug)]
+pub struct KeyBuilder {
+    bits: usize,
+    exponent: BigUint,
+    method: PrimeMethod,
+}
 
-    // 4. Choose an integer e s.t. 1 < e < lambda_n, and s.t. e and lambda_n are co-prime
-    let mut exponent = BigUint::from_i32(65_537).unwrap();
-    assert!(one.clone() < exponent.clone() && exponent.clone() < lambda_n.clone());
-    assert_eq!(_gcd(exponent.clone(), lambda_n.clone()), One::one());
+impl KeyBuilder {
+    /// Returns a builder for RSA-1024 keys with public exponent 65537
+    pub fn new() -> KeyBuilder {
+        KeyBuilder {
+            bits: KEY_SIZE,
+            exponent: BigUint::from_u32(DEFAULT_EXPONENT).unwrap(),
+            method: PrimeMethod::Random,
+        }
+    }
 
-    // 5. Compute d s.t. d * e ≡ 1 mod lambda_n. d is modular multiplicative inverse of e, lambda_n
-    // d is the private key exponent
-    let d: BigUint = _modular_multiplicative_inverse(exponent.clone(), lambda_n.clone());
+    /// Sets the length of the modulus in bits. Real keys should use 2048, 3072 or 4096; anything from
+    /// `MIN_KEY_SIZE` up is accepted so that small keys can be used for teaching.
+    pub fn bits(mut self, bits: usize) -> KeyBuilder {
+        self.bits = bits;
+        self
+    }
 
-    // Return tuple of (public_key, private_key)
-    ((&prime_one * &prime_two, exponent), d)
+    /// Sets the public exponent, which must be odd and at least 3
+    pub fn exponent(mut self, exponent: BigUint) -> KeyBuilder {
+        self.exponent = exponent;
+        self
+    }
+
+    /// Sets how the primes are chosen
+    pub fn prime_method(mut self, method: PrimeMethod) -> KeyBuilder {
+        self.method = method;
+        self
+    }
+
+    /// Generates a new key, drawing the primes from `rng`, which should be a secure generator such as
+    /// `rand::ChaChaRng`. Fails with `Error::InvalidParameters` if the key is smaller than
+    /// `MIN_KEY_SIZE`, the exponent is even, less than 3, or too large for the key, or the parameters
+    /// don't suit the prime method.
+    pub fn build<R: RandomSource>(&self, rng: &mut R) -> Result<RsaPrivateKey, Error> {
+        self.build_with_certificates(rng).map(|(key, _)| key)
+    }
+
+    /// Generates a new key as `build` does, also returning certificates for its primes `p` and `q`
+    /// (in that order) when the prime method is `PrimeMethod::Provable`. Other methods return no
+    /// certificates.
+    pub fn build_with_certificates<R: RandomSource>(&self, rng: &mut R)
+                                                    -> Result<(RsaPrivateKey, Vec<PrimeCertificate>), Error> {
+        let three = BigUint::from_u32(3).unwrap();
+        if self.bits < MIN_KEY_SIZE || self.exponent < three || self.exponent.is_even()
+            || self.exponent.bits() > self.bits / 2 {
+            return Err(Error::InvalidParameters);
+        }
+        if self.method == PrimeMethod::Fips186 && (!self.bits.is_multiple_of(2) || self.exponent.bits() <= 16
+                                                   || self.exponent.bits() > 256) {
+            return Err(Error::InvalidParameters);
+        }
+        if (self.method == PrimeMethod::Safe || self.method == PrimeMethod::Strong)
+            && self.bits < 2 * primes::MIN_STRONG_PRIME_BITS {
+            return Err(Error::InvalidParameters);
+        }
+
+        Ok(_gen_key(self.bits, &self.exponent, self.method, rng))
+    }
 }
 
-pub fn gen_key() -> ((BigUint, BigUint), BigUint) {
-    _gen_key(KEY_SIZE / 2)
+impl Default for KeyBuilder {
+    fn default() -> KeyBuilder {
+        KeyBuilder::new()
+    }
+}
+
+/// Generates a new RSA-1024 private key
+pub fn gen_key() -> RsaPrivateKey {
+    KeyBuilder::new().build(&mut rand::new_secure()).expect("Default parameters are always valid")
 }
 
-/// Helper function, encrypts bytes contained in blocks using the given publickey, returns cipher as
-/// a vector of `BigUint`
+/// Helper function, encrypts message blocks using the given public key, returning the cipher blocks
 ///
 /// # Arguments
-///     * `blocks` - Packed vector of `u32`. The encryption algorith is run on each block, resulting in a
-///             corresponding output block in the returned vector
-///     * `key` - Public key to encrypt with, tuple of the form (modulus, exponent) where both are BigUints
-fn _encrypt_bytes(blocks: Vec<u32>, key: (BigUint, BigUint)) -> Vec<BigUint> {
-    print!("Pre-Encrypt Blocks: [ ");
-    for b in &blocks { print!("{:#b}, ", b); }
-    println!("]");
+/// * `blocks` - Message blocks, each less than the modulus. The encryption algorithm is run on each
+///   block, resulting in a corresponding output block in the returned vector
+/// * `key` - Public key to encrypt with
+fn _encrypt_bytes(blocks: &[BigUint], key: &RsaPublicKey) -> Vec<BigUint> {
+    blocks.iter().map(|block| rsaep(block, key)).collect()
+}
 
-    let mut output: Vec<BigUint> = vec![BigUint::from_i32(0).unwrap();
-                                        blocks.len()];
-    for (i, block) in blocks.iter().enumerate() {
-        output[i] = BigUint::from_u32(block.clone()).unwrap()
-            .modpow(&key.1.clone(), &key.0.clone());
+/// Helper function, decrypts cipher blocks using the given private key, returning the message blocks
+///
+/// # Arguments
+/// * `cipher` - The cipher to decrypt, as a slice of BigUint encrypted blocks
+/// * `privkey` - The private key to use when decrypting the given cipher
+fn _decrypt_bytes(cipher: &[BigUint], privkey: &RsaPrivateKey) -> Vec<BigUint> {
+    cipher.iter().map(|block| rsadp(block, privkey)).collect()
+}
+
+/// Helper function, packs `msg` into blocks of `capacity` bytes to pass to the encryption function.
+/// The message is preceded by its length as a big-endian `u64`, and the last block is filled out
+/// with zeros, so that `_unpack_bytes` can tell where the message ends even if it ends in zeros.
+///
+/// # Arguments
+/// * `msg` - Bytes to pack
+/// * `capacity` - Bytes per block, less than the length of the modulus so every block is below it
+pub(crate) fn _pack_bytes(msg: &[u8], capacity: usize) -> Vec<BigUint> {
+    let mut framed = (msg.len() as u64).to_be_bytes().to_vec();
+    framed.extend_from_slice(msg);
+
+    framed.chunks(capacity)
+        .map(|chunk| {
+            let mut block = chunk.to_vec();
+            block.resize(capacity, 0);
+            os2ip(&block)
+        })
+        .collect()
+}
+
+/// Helper function, unpacks and returns the message from blocks made by `_pack_bytes`. Returns
+/// `None` if the blocks don't hold a well-formed message, as happens when decrypting with the
+/// wrong key.
+///
+/// # Arguments
+/// * `blocks` - Blocks to unpack the message from
+/// * `capacity` - Bytes per block, as given to `_pack_bytes`
+pub(crate) fn _unpack_bytes(blocks: &[BigUint], capacity: usize) -> Option<Vec<u8>> {
+    let mut framed = Vec::with_capacity(blocks.len() * capacity);
+    for block in blocks {
+        framed.extend(i2osp(block, capacity)?);
+    }
+    if framed.len() < LENGTH_PREFIX_SIZE {
+        return None;
     }
 
-    output
+    let (prefix, msg) = framed.split_at(LENGTH_PREFIX_SIZE);
+    let len = usize::try_from(u64::from_be_bytes(prefix.try_into().unwrap())).ok()?;
+    // Everything after the message must be the zeros filling out the last block
+    if len > msg.len() || msg.len() - len >= capacity || msg[len..].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    Some(msg[..len].to_vec())
 }
 
-/// Helper function, decrypts cipher blocks using the given private key and exponent, returning the decrypted
-/// blocks as a vector of `u32`
+/// Returns the length in bytes of the longest message that `encrypt` fits in a single block under
+/// `key`
+pub fn single_block_message_len(key: &RsaPublicKey) -> usize {
+    key.size().saturating_sub(1 + LENGTH_PREFIX_SIZE)
+}
+
+/// Encrypts `msg` using given public key, without padding. The message and its length are split
+/// into blocks one byte shorter than the modulus, and each block is encrypted to a block as long as
+/// the modulus, so any bytes at all round-trip exactly through `decrypt`. Like `encrypt_str`, this
+/// is textbook RSA and is only fit for teaching: equal blocks give equal ciphers. Fails with
+/// `Error::KeyTooSmall` if the modulus is a single byte long, leaving no room in a block.
 ///
 /// # Arguments
-///     * `cipher` - The cipher to decrypt, as a reference to a vector of BigUint encrypted blocks
-///     * `privkey` - The private key to use when decrypting the given cipher
-///     * `modulus` - The "modulus" component of the public key, also used for decryption
-fn _decrypt_bytes(cipher: &Vec<BigUint>, privkey: BigUint, modulus: BigUint) -> Vec<u32> {
-    let mut dec_blocks = vec![0_u32; cipher.len()];
-    for (i, enc_block) in cipher.iter().enumerate() {
-        match enc_block.modpow(&privkey.clone(), &modulus.clone())
-            .to_u32() {
-            Some(thing) => dec_blocks[i] = thing,
-            None =>  {
-                println!("> Error: Found garbage value when attempting to decrypt. Your key is probably incorrect.");
-                dec_blocks[i] = 0;
-            }
-        }
+/// * `msg` - Bytes to encrypt
+/// * `pubkey` - Public key to use to encrypt `msg`
+pub fn encrypt(msg: &[u8], pubkey: &RsaPublicKey) -> Result<Vec<u8>, Error> {
+    let size = pubkey.size();
+    if size < 2 {
+        return Err(Error::KeyTooSmall);
     }
+    Ok(_encrypt_bytes(&_pack_bytes(msg, size - 1), pubkey).iter()
+        .flat_map(|block| i2osp(block, size).expect("Cipher blocks are reduced mod n"))
+        .collect())
+}
 
-    print!("Post-Decrypt Blocks: [ ");
-    for b in &dec_blocks { print!("{:#b}, ", b); }
-    println!("]");
+/// Returns cipher produced by `encrypt` decrypted. Fails with `Error::Decryption` if the cipher
+/// isn't a whole number of blocks, or doesn't decrypt to a well-formed message.
+///
+/// # Arguments
+/// * `cipher` - Bytes to decrypt
+/// * `privkey` - The private key to use for decryption
+pub fn decrypt(cipher: &[u8], privkey: &RsaPrivateKey) -> Result<Vec<u8>, Error> {
+    let size = privkey.size();
+    if !cipher.len().is_multiple_of(size) {
+        return Err(Error::Decryption);
+    }
+    let blocks: Vec<BigUint> = cipher.chunks(size).map(os2ip).collect();
+    if blocks.iter().any(|block| *block >= privkey.n) {
+        return Err(Error::Decryption);
+    }
 
-    dec_blocks
+    _unpack_bytes(&_decrypt_bytes(&blocks, privkey), size - 1).ok_or(Error::Decryption)
 }
 
-/// Helper function, packs a string into a vector of `u32` (which is the return value) to pass to encryption
-/// function
+/// Encrypts string `msg` using given public key with `encrypt`, returning each block of the cipher
+/// as a `BigUint`. Fails with `Error::KeyTooSmall` if `encrypt` does.
 ///
 /// # Arguments
-///     * `msg` - The string to pack into `Vec<u32>`
-fn _pack_string(msg: &str) -> Vec<u32> {
-    let offset: usize = if (msg.len() % BLOCK_SIZE) == 0 { 0 } else { 1 };
+/// * `msg` - String to encrypt
+/// * `pubkey` - Public key to use to encrypt `msg`
+pub fn encrypt_str(msg: &str, pubkey: &RsaPublicKey) -> Result<Vec<BigUint>, Error> {
+    Ok(encrypt(msg.as_bytes(), pubkey)?.chunks(pubkey.size()).map(os2ip).collect())
+}
 
-    let mut blocks = vec![0_u32; (msg.len() / BLOCK_SIZE) + offset];
-    let mut block_index = 0;
-    let mut block_count = 0;
-    for c in msg.chars() {
-        blocks[block_index] ^= c as u32;
-        block_count += 1;
-        if block_count == BLOCK_SIZE {
-            block_index += 1;
-            block_count = 0;
-        } else {
-            blocks[block_index] <<= 8;
-        }
+/// Returns cipher produced by `encrypt_str` decrypted as string. Fails with `Error::Decryption` if
+/// `decrypt` does or the result is not valid UTF-8.
+///
+/// # Arguments
+/// * `cipher` - Vector of `BigUint` representing encrypted string
+/// * `privkey` - The private key to use for decryption
+pub fn decrypt_str(cipher: &[BigUint], privkey: &RsaPrivateKey) -> Result<String, Error> {
+    let mut bytes = Vec::new();
+    for block in cipher {
+        bytes.extend(i2osp(block, privkey.size()).ok_or(Error::Decryption)?);
     }
 
-    blocks
+    String::from_utf8(decrypt(&bytes, privkey)?).map_err(|_| Error::Decryption)
 }
 
-/// Helper function, unpacks and returns a string from a vector of `u32` returned by decryption function.
+/// Encrypts string `msg` using given public key with RSAES-PKCS1-v1_5 padding. The message is split
+/// into chunks that fit in a single padded block, and each chunk is encrypted separately. Fails with
+/// `Error::MessageTooLong` if the key is too small to hold any message at all.
 ///
 /// # Arguments
-///     * `blocks` - `Vec<u32>` to unpack characters from
-fn _unpack_string(mut blocks: Vec<u32>) -> String {
-    let mut res: String = String::new();
-    for mut c in blocks.iter_mut().rev() {
-        let mut c = *c;
-        while c > 0 {
-            let new_char = (c & 0b11111111) as u8;
-            res = char::from(new_char).to_string().to_owned() + &res;
-            // Mask to get rid of the bytes representing the character we just pulled out of this u32
-            c &= 0b11111111111111111111111100000000;
-            c = c.overflowing_shr(8).0;
-        }
+/// * `msg` - String to encrypt
+/// * `pubkey` - Public key to use to encrypt `msg`
+/// * `rng` - Random number generator used for the padding bytes
+pub fn encrypt_str_pkcs1v15<R: RandomSource>(msg: &str, pubkey: &RsaPublicKey, rng: &mut R) -> Result<Vec<BigUint>, Error> {
+    let chunk_len = pkcs1::pkcs1v15_max_message_len(pubkey);
+    if chunk_len == 0 {
+        return Err(Error::MessageTooLong);
     }
 
-    res
+    msg.as_bytes().chunks(chunk_len)
+        .map(|chunk| pkcs1::encrypt_pkcs1v15(chunk, pubkey, rng).map(|block| os2ip(&block)))
+        .collect()
 }
 
-/// Encrypts string `msg` using given public key
+/// Returns cipher produced by `encrypt_str_pkcs1v15` decrypted and unpacked as string. Fails with
+/// `Error::Decryption` if any block has invalid padding or the result is not valid UTF-8.
 ///
 /// # Arguments
-///     * `msg` - String to encrypt
-///     * `pubkey` - Publickey to use to encrypt `msg`, in format (modulus: `BigUint`, exponent: `BigUint')
-pub fn encrypt_str(msg: &str, pubkey: (BigUint, BigUint)) -> Vec<BigUint> {
-    let packed_string = _pack_string(msg);
-    _encrypt_bytes(packed_string, pubkey)
+/// * `cipher` - Vector of `BigUint` representing encrypted string
+/// * `privkey` - The private key to use for decryption
+pub fn decrypt_str_pkcs1v15(cipher: &[BigUint], privkey: &RsaPrivateKey) -> Result<String, Error> {
+    let mut msg = Vec::new();
+    for block in cipher {
+        let block = i2osp(block, privkey.size()).ok_or(Error::Decryption)?;
+        msg.extend(pkcs1::decrypt_pkcs1v15(&block, privkey)?);
+    }
+
+    String::from_utf8(msg).map_err(|_| Error::Decryption)
 }
 
-/// Returns cipher decrypted and unpacked as string
+/// Encrypts `msg` with RSAES-OAEP, using `hash` for both the label and MGF1. The result is a single
+/// block the length of the modulus, in the standard format accepted by other RSA implementations.
 ///
 /// # Arguments
-///     * `cipher` - Vector of `BigUint` representing encrypted string
-///     * `privkey` - The private key to use for decryption
-///     * `modulus` - The modulus component of the public key, also used in decryption
-pub fn decrypt_str(cipher: &Vec<BigUint>, privkey: BigUint, modulus: BigUint) -> String {
-    let dec_blocks = _decrypt_bytes(cipher, privkey, modulus);
-    _unpack_string(dec_blocks)
+/// * `msg` - Bytes to encrypt, at most `pkcs1::oaep_max_message_len` bytes long
+/// * `pubkey` - Public key to use to encrypt `msg`
+/// * `hash` - Hash function to use
+/// * `label` - Label associated with the message. Pass an empty slice for no label.
+/// * `rng` - Random number generator used for the seed
+pub fn encrypt_oaep<R: RandomSource>(msg: &[u8], pubkey: &RsaPublicKey, hash: HashAlgorithm, label: &[u8],
+                                     rng: &mut R) -> Result<Vec<u8>, Error> {
+    pkcs1::encrypt_oaep(msg, pubkey, hash.hasher().as_mut(), label, rng)
 }
 
-pub fn test_thing() {
-    let (pubkey, privkey) = _gen_key(KEY_SIZE / 2);
-    let cipher = encrypt_str(&"Hello world, how are you today?".to_string(), pubkey.clone());
-    let dec_result = decrypt_str(&cipher, privkey, pubkey.0);
+/// Decrypts a cipher produced by `encrypt_oaep`
+///
+/// # Arguments
+/// * `cipher` - Cipher to decrypt, exactly as long as the modulus
+/// * `privkey` - The private key to use for decryption
+/// * `hash` - Hash function used when encrypting
+/// * `label` - Label used when encrypting
+pub fn decrypt_oaep(cipher: &[u8], privkey: &RsaPrivateKey, hash: HashAlgorithm,
+                    label: &[u8]) -> Result<Vec<u8>, Error> {
+    pkcs1::decrypt_oaep(cipher, privkey, hash.hasher().as_mut(), label)
+}
 
-    println!("Result: {}", dec_result);
-}
\ No newline at end of file
+/// Encrypts string `msg` using given public key with RSAES-OAEP and no label. As with
+/// `encrypt_str_pkcs1v15`, the message is split into chunks that each fit in a single block, and
+/// the key must be large enough to hold at least one byte.
+///
+/// # Arguments
+/// * `msg` - String to encrypt
+/// * `pubkey` - Public key to use to encrypt `msg`
+/// * `hash` - Hash function to use
+/// * `rng` - Random number generator used for the seeds
+pub fn encrypt_str_oaep<R: RandomSource>(msg: &str, pubkey: &RsaPublicKey, hash: HashAlgorithm,
+                                         rng: &mut R) -> Result<Vec<BigUint>, Error> {
+    let chunk_len = pkcs1::oaep_max_message_len(pubkey, hash.hasher().as_ref());
+    if chunk_len == 0 {
+        return Err(Error::MessageTooLong);
+    }
+
+    msg.as_bytes().chunks(chunk_len)
+        .map(|chunk| encrypt_oaep(chunk, pubkey, hash, &[], rng).map(|block| os2ip(&block)))
+        .collect()
+}
+
+/// Returns cipher produced by `encrypt_str_oaep` decrypted and unpacked as string
+///
+/// # Arguments
+/// * `cipher` - Vector of `BigUint` representing encrypted string
+/// * `privkey` - The private key to use for decryption
+/// * `hash` - Hash function used when encrypting
+pub fn decrypt_str_oaep(cipher: &[BigUint], privkey: &RsaPrivateKey, hash: HashAlgorithm) -> Result<String, Error> {
+    let mut msg = Vec::new();
+    for block in cipher {
+        let block = i2osp(block, privkey.size()).ok_or(Error::Decryption)?;
+        msg.extend(decrypt_oaep(&block, privkey, hash, &[])?);
+    }
+
+    String::from_utf8(msg).map_err(|_| Error::Decryption)
+}
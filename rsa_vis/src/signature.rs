@@ -0,0 +1,199 @@
+use crate::rand::RandomSource;
+use crate::pkcs1;
+use crate::hash::HashAlgorithm;
+use crate::rsa::{self, Error, RsaPublicKey, RsaPrivateKey};
+
+/// DER encoding of the DigestInfo header for each hash, to be followed by the digest itself
+/// (RFC 8017 section 9.2, note 1)
+fn _digest_info_prefix(hash: HashAlgorithm) -> &'static [u8] {
+    match hash {
+        HashAlgorithm::Sha1 => &[0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05,
+                                 0x00, 0x04, 0x14],
+        HashAlgorithm::Sha256 => &[0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03,
+                                   0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20],
+        HashAlgorithm::Sha384 => &[0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03,
+                                   0x04, 0x02, 0x02, 0x05, 0x00, 0x04, 0x30],
+        HashAlgorithm::Sha512 => &[0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03,
+                                   0x04, 0x02, 0x03, 0x05, 0x00, 0x04, 0x40],
+    }
+}
+
+/// Helper function, EMSA-PKCS1-v1_5 encoding (RFC 8017 section 9.2). Returns the `em_len` byte block
+/// 0x00 || 0x01 || PS || 0x00 || DigestInfo, where PS is made up of 0xff bytes.
+fn _emsa_pkcs1v15_encode(msg: &[u8], em_len: usize, hash: HashAlgorithm) -> Result<Vec<u8>, Error> {
+    let mut t = _digest_info_prefix(hash).to_vec();
+    t.extend(hash.digest(msg));
+    if em_len < t.len() + 11 {
+        return Err(Error::KeyTooSmall);
+    }
+
+    let mut em = vec![0x00_u8, 0x01];
+    em.extend(vec![0xff_u8; em_len - t.len() - 3]);
+    em.push(0x00);
+    em.extend(t);
+    Ok(em)
+}
+
+/// Helper function, converts signature `sig` to an integer and raises it to the public exponent
+/// (RSAVP1, RFC 8017 section 5.2.2). Fails if the signature isn't the length of the modulus or is
+/// out of range.
+fn _signature_representative(sig: &[u8], key: &RsaPublicKey) -> Result<num::BigUint, Error> {
+    if sig.len() != key.size() {
+        return Err(Error::Verification);
+    }
+    let s = rsa::os2ip(sig);
+    if s >= key.n {
+        return Err(Error::Verification);
+    }
+
+    Ok(rsa::rsaep(&s, key))
+}
+
+/// Signs `msg` with RSASSA-PKCS1-v1_5 (RFC 8017 section 8.2.1), returning a signature the same length
+/// as the modulus
+///
+/// # Arguments
+/// * `msg` - Message to sign
+/// * `key` - Private key to sign with
+/// * `hash` - Hash function to digest `msg` with
+pub fn sign_pkcs1v15(msg: &[u8], key: &RsaPrivateKey, hash: HashAlgorithm) -> Result<Vec<u8>, Error> {
+    let k = key.size();
+    let em = _emsa_pkcs1v15_encode(msg, k, hash)?;
+
+    // RSASP1 is the same operation as RSADP
+    let s = rsa::rsadp(&rsa::os2ip(&em), key);
+    Ok(rsa::i2osp(&s, k).expect("Signature is always smaller than the modulus"))
+}
+
+/// Verifies an RSASSA-PKCS1-v1_5 signature (RFC 8017 section 8.2.2). Returns `Error::Verification`
+/// for any malformed or non-matching signature.
+///
+/// # Arguments
+/// * `msg` - Message that was signed
+/// * `sig` - Signature to check
+/// * `key` - Public key of the signer
+/// * `hash` - Hash function used when signing
+pub fn verify_pkcs1v15(msg: &[u8], sig: &[u8], key: &RsaPublicKey, hash: HashAlgorithm) -> Result<(), Error> {
+    let k = key.size();
+    let m = _signature_representative(sig, key)?;
+    let em = rsa::i2osp(&m, k).ok_or(Error::Verification)?;
+    let expected = _emsa_pkcs1v15_encode(msg, k, hash).map_err(|_| Error::Verification)?;
+
+    if em == expected {
+        Ok(())
+    } else {
+        Err(Error::Verification)
+    }
+}
+
+/// Helper function, returns the hash of M' = (0x)00 00 00 00 00 00 00 00 || mHash || salt, as used by
+/// both halves of EMSA-PSS
+fn _pss_hash(m_hash: &[u8], salt: &[u8], hash: HashAlgorithm) -> Vec<u8> {
+    let mut hasher = hash.hasher();
+    hasher.update(&[0_u8; 8]);
+    hasher.update(m_hash);
+    hasher.update(salt);
+    hasher.finalize_reset()
+}
+
+/// Helper function, EMSA-PSS encoding (RFC 8017 section 9.1.1) of `msg` into an integer of at most
+/// `em_bits` bits
+fn _emsa_pss_encode<R: RandomSource>(msg: &[u8], em_bits: usize, hash: HashAlgorithm, salt_len: usize,
+                                     rng: &mut R) -> Result<Vec<u8>, Error> {
+    let h_len = hash.output_len();
+    let em_len = em_bits.div_ceil(8);
+    if em_len < h_len + 2 || salt_len > em_len - h_len - 2 {
+        return Err(Error::KeyTooSmall);
+    }
+
+    let m_hash = hash.digest(msg);
+    let mut salt = vec![0_u8; salt_len];
+    rng.fill_bytes(&mut salt);
+    let h = _pss_hash(&m_hash, &salt, hash);
+
+    // DB = PS || 0x01 || salt, where PS is made up of zero bytes
+    let mut db = vec![0_u8; em_len - salt_len - h_len - 2];
+    db.push(0x01);
+    db.extend(salt);
+    let db_mask = pkcs1::mgf1(&h, em_len - h_len - 1, hash.hasher().as_mut());
+    for (d, m) in db.iter_mut().zip(db_mask.iter()) {
+        *d ^= m;
+    }
+    db[0] &= 0xff >> (8 * em_len - em_bits);
+
+    // EM = maskedDB || H || 0xbc
+    let mut em = db;
+    em.extend(h);
+    em.push(0xbc);
+    Ok(em)
+}
+
+/// Helper function, EMSA-PSS verification (RFC 8017 section 9.1.2). Returns whether `em` is a valid
+/// encoding of `msg`.
+fn _emsa_pss_verify(msg: &[u8], em: &[u8], em_bits: usize, hash: HashAlgorithm, salt_len: usize) -> bool {
+    let h_len = hash.output_len();
+    let em_len = em_bits.div_ceil(8);
+    // Compared this way round so that a huge `salt_len` can't overflow
+    if em.len() != em_len || em_len < h_len + 2 || salt_len > em_len - h_len - 2 || em[em_len - 1] != 0xbc {
+        return false;
+    }
+
+    let (masked_db, h) = em[..em_len - 1].split_at(em_len - h_len - 1);
+    let unused_bits = 8 * em_len - em_bits;
+    if masked_db[0] & !(0xff >> unused_bits) != 0 {
+        return false;
+    }
+
+    let db_mask = pkcs1::mgf1(h, em_len - h_len - 1, hash.hasher().as_mut());
+    let mut db: Vec<u8> = masked_db.iter().zip(db_mask.iter()).map(|(d, m)| d ^ m).collect();
+    db[0] &= 0xff >> unused_bits;
+
+    let ps_len = em_len - h_len - salt_len - 2;
+    if db[..ps_len].iter().any(|b| *b != 0) || db[ps_len] != 0x01 {
+        return false;
+    }
+
+    let salt = &db[db.len() - salt_len..];
+    _pss_hash(&hash.digest(msg), salt, hash) == h
+}
+
+/// Signs `msg` with RSASSA-PSS (RFC 8017 section 8.1.1) using MGF1 over the same hash, returning a
+/// signature the same length as the modulus
+///
+/// # Arguments
+/// * `msg` - Message to sign
+/// * `key` - Private key to sign with
+/// * `hash` - Hash function to digest `msg` with
+/// * `salt_len` - Length of the random salt in bytes. The usual choice is the length of the digest.
+/// * `rng` - Random number generator used for the salt
+pub fn sign_pss<R: RandomSource>(msg: &[u8], key: &RsaPrivateKey, hash: HashAlgorithm, salt_len: usize,
+                                 rng: &mut R) -> Result<Vec<u8>, Error> {
+    let mod_bits = key.n.bits();
+    let em = _emsa_pss_encode(msg, mod_bits - 1, hash, salt_len, rng)?;
+
+    // RSASP1 is the same operation as RSADP
+    let s = rsa::rsadp(&rsa::os2ip(&em), key);
+    Ok(rsa::i2osp(&s, key.size()).expect("Signature is always smaller than the modulus"))
+}
+
+/// Verifies an RSASSA-PSS signature (RFC 8017 section 8.1.2). Returns `Error::Verification` for any
+/// malformed or non-matching signature.
+///
+/// # Arguments
+/// * `msg` - Message that was signed
+/// * `sig` - Signature to check
+/// * `key` - Public key of the signer
+/// * `hash` - Hash function used when signing
+/// * `salt_len` - Salt length used when signing
+pub fn verify_pss(msg: &[u8], sig: &[u8], key: &RsaPublicKey, hash: HashAlgorithm,
+                  salt_len: usize) -> Result<(), Error> {
+    let em_bits = key.n.bits() - 1;
+    let m = _signature_representative(sig, key)?;
+    let em = rsa::i2osp(&m, em_bits.div_ceil(8)).ok_or(Error::Verification)?;
+
+    if _emsa_pss_verify(msg, &em, em_bits, hash, salt_len) {
+        Ok(())
+    } else {
+        Err(Error::Verification)
+    }
+}
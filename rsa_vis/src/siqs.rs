@@ -0,0 +1,699 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use num::{BigInt, BigUint, Integer, ToPrimitive};
+use num::bigint::Sign;
+use num::traits::{One, Signed, Zero};
+
+use crate::factor;
+use crate::primes;
+use crate::rand::RandomSource;
+
+/// Sieve parameters by size of the number: for numbers of up to each number of bits, the number of
+/// primes in the factor base and the half-width M of the sieve interval [-M, M). Sizes in between
+/// are interpolated.
+const PARAMETERS: [(usize, usize, usize); 12] = [
+    (60, 60, 8192),
+    (80, 100, 16384),
+    (100, 200, 32768),
+    (120, 300, 32768),
+    (140, 450, 32768),
+    (160, 1000, 32768),
+    (180, 2000, 32768),
+    (200, 2500, 32768),
+    (230, 7000, 32768),
+    (260, 10000, 32768),
+    (300, 15000, 65536),
+    (350, 20000, 65536),
+];
+
+/// Smallest number `quadratic_sieve` will try; anything smaller is quicker to split with Pollard's rho
+pub const MIN_SIEVE_BITS: usize = 40;
+
+/// Largest number `quadratic_sieve` will try, the top of `PARAMETERS`
+pub const MAX_SIEVE_BITS: usize = 350;
+
+/// Relations collected beyond the size of the factor base, each of which adds a dependency for the
+/// linear algebra to try
+const EXTRA_RELATIONS: usize = 32;
+
+/// Factor base primes below this aren't sieved with. They hit so many positions that sieving with
+/// them costs more than it helps; the threshold is lowered to make up for their missing logs.
+const MIN_SIEVED_PRIME: u32 = 40;
+
+/// Leftover cofactors up to this multiple of the largest factor base prime are kept as partial
+/// relations, which make a full relation once another partial with the same large prime turns up
+const LARGE_PRIME_MULTIPLIER: u64 = 1024;
+
+/// Largest sieve threshold. Logs are scaled down for big numbers to keep the threshold below this, so
+/// that values can start at 128 - threshold without overflowing.
+const MAX_THRESHOLD: u8 = 96;
+
+/// Bits taken off the sieve threshold to allow for the logs of unsieved small primes and rounding
+const THRESHOLD_SLACK: f64 = 16.0;
+
+/// Squarefree multipliers k tried by `_choose_multiplier`
+const MULTIPLIERS: [u32; 24] = [1, 3, 5, 7, 11, 13, 15, 17, 19, 21, 23, 29, 31, 33, 35, 37, 39, 41, 43, 47, 51,
+                                53, 55, 57];
+
+/// Progress of `quadratic_sieve`, reported after every batch of polynomials so that the relations
+/// collected can be watched or charted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SieveProgress {
+    /// Time since sieving started
+    pub elapsed: Duration,
+    /// Polynomials sieved so far
+    pub polynomials: u64,
+    /// Relations that factored completely over the factor base
+    pub full_relations: usize,
+    /// Relations made by pairing up partial relations with the same large prime
+    pub combined_relations: usize,
+    /// Partial relations found, each with one prime outside the factor base
+    pub partial_relations: usize,
+    /// Relations needed before the linear algebra can start
+    pub target: usize,
+}
+
+impl SieveProgress {
+    /// Returns the number of usable relations, full and combined
+    pub fn relations(&self) -> usize {
+        self.full_relations + self.combined_relations
+    }
+}
+
+/// Primes that Q(x) values are factored over: the odd primes p for which kN is a square mod p, with a
+/// square root of kN mod p and log2(p), scaled and rounded, for the sieve
+struct FactorBase {
+    primes: Vec<u32>,
+    roots: Vec<u32>,
+    logs: Vec<u8>,
+    /// 2^64 / p rounded up, for `_fast_mod`
+    reciprocals: Vec<u64>,
+}
+
+impl FactorBase {
+    /// Returns the prime for column `column` of the exponent matrix. Column 0 stands for -1, column
+    /// 1 for 2 and the rest for the odd primes in order.
+    fn prime(&self, column: usize) -> u32 {
+        match column {
+            0 => 0,
+            1 => 2,
+            _ => self.primes[column - 2],
+        }
+    }
+}
+
+/// A relation u^2 = product of `factors` (as matrix columns, with repeats) times `square`^2 (mod n)
+#[derive(Clone, Debug)]
+struct Relation {
+    u: BigUint,
+    factors: Vec<usize>,
+    square: BigUint,
+}
+
+/// Helper function, returns a^e mod m for word-sized numbers
+fn _pow_mod(a: u64, mut e: u64, m: u64) -> u64 {
+    let mut result = 1_u64;
+    let mut base = a % m;
+    while e > 0 {
+        if e & 1 == 1 {
+            result = (result as u128 * base as u128 % m as u128) as u64;
+        }
+        base = (base as u128 * base as u128 % m as u128) as u64;
+        e >>= 1;
+    }
+    result
+}
+
+/// Helper function, returns `a` mod `p` given `reciprocal` = 2^64 / p rounded up, with two multiplications
+/// instead of a division (Lemire, Kaser and Kurz, "Faster remainder by direct computation")
+fn _fast_mod(a: u32, p: u32, reciprocal: u64) -> u32 {
+    let fraction = reciprocal.wrapping_mul(a as u64);
+    ((fraction as u128 * p as u128) >> 64) as u32
+}
+
+/// Helper function, returns the inverse of `a` mod `m`. `a` and `m` must be coprime.
+fn _inverse_mod(a: u64, m: u64) -> u64 {
+    let (mut r0, mut r1) = (m as i64, (a % m) as i64);
+    let (mut t0, mut t1) = (0_i64, 1_i64);
+    while r1 != 0 {
+        let q = r0 / r1;
+        let r2 = r0 - q * r1;
+        r0 = r1;
+        r1 = r2;
+        let t2 = t0 - q * t1;
+        t0 = t1;
+        t1 = t2;
+    }
+    t0.rem_euclid(m as i64) as u64
+}
+
+/// Helper function, returns a square root of `a` mod odd prime `p` by Tonelli-Shanks. `a` must be a
+/// square mod `p`.
+fn _sqrt_mod(a: u64, p: u64) -> u64 {
+    let a = a % p;
+    if a == 0 {
+        return 0;
+    }
+    if p % 4 == 3 {
+        return _pow_mod(a, (p + 1) / 4, p);
+    }
+
+    // p - 1 = q 2^s with q odd
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    let z = (2..p).find(|z| _pow_mod(*z, (p - 1) / 2, p) == p - 1).expect("Every odd prime has a non-residue");
+
+    let mut m = s;
+    let mut c = _pow_mod(z, q, p);
+    let mut t = _pow_mod(a, q, p);
+    let mut r = _pow_mod(a, q.div_ceil(2), p);
+    while t != 1 {
+        // Least i with t^(2^i) = 1
+        let mut i = 0;
+        let mut t2 = t;
+        while t2 != 1 {
+            t2 = t2 * t2 % p;
+            i += 1;
+        }
+        let b = _pow_mod(c, 1 << (m - i - 1), p);
+        m = i;
+        c = b * b % p;
+        t = t * c % p;
+        r = r * b % p;
+    }
+    r
+}
+
+/// Helper function, chooses the multiplier k by the Knuth-Schroeppel function, which favours kN
+/// that are squares modulo many small primes, and so have many small primes in the factor base
+fn _choose_multiplier(n: &BigUint) -> u32 {
+    let small_primes: Vec<u64> = primes::primes_in_range(3, 1000).collect();
+    let n_mod_8 = (n % 8_u32).to_u64().unwrap();
+
+    let score = |k: u32| {
+        let k = k as u64;
+        let mut score = -0.5 * (k as f64).ln();
+        score += match k * n_mod_8 % 8 {
+            1 => 2.0 * 2_f64.ln(),
+            5 => 2_f64.ln(),
+            _ => 0.5 * 2_f64.ln(),
+        };
+        for p in &small_primes {
+            let p = *p;
+            let residue = k * (n % p).to_u64().unwrap() % p;
+            if residue == 0 {
+                score += (p as f64).ln() / p as f64;
+            } else if _pow_mod(residue, (p - 1) / 2, p) == 1 {
+                score += 2.0 * (p as f64).ln() / (p - 1) as f64;
+            }
+        }
+        score
+    };
+
+    MULTIPLIERS.iter().cloned().max_by(|a, b| score(*a).partial_cmp(&score(*b)).unwrap()).unwrap()
+}
+
+/// Helper function, returns the factor base of `size` primes for kN, or a factor of `n` if one of the
+/// primes divides it
+fn _factor_base(n: &BigUint, kn: &BigUint, size: usize) -> Result<FactorBase, BigUint> {
+    let mut factor_base = FactorBase { primes: Vec::new(), roots: Vec::new(), logs: Vec::new(), reciprocals: Vec::new() };
+    for p in primes::primes_in_range(3, 1 << 32) {
+        if factor_base.primes.len() >= size {
+            break;
+        }
+        if (n % p).is_zero() && *n != BigUint::from(p) {
+            return Err(BigUint::from(p));
+        }
+
+        let residue = (kn % p).to_u64().unwrap();
+        if residue == 0 || _pow_mod(residue, (p - 1) / 2, p) == 1 {
+            factor_base.primes.push(p as u32);
+            factor_base.roots.push(_sqrt_mod(residue, p) as u32);
+            factor_base.reciprocals.push(u64::MAX / p + 1);
+        }
+    }
+    Ok(factor_base)
+}
+
+/// Helper function, interpolates the factor base size and sieve half-width for a number of `bits`
+/// bits from `PARAMETERS`
+fn _parameters(bits: usize) -> (usize, usize) {
+    let mut below = PARAMETERS[0];
+    for above in PARAMETERS.iter() {
+        if bits <= above.0 {
+            if above.0 == below.0 {
+                return (above.1, above.2);
+            }
+            let t = (bits - below.0) as f64 / (above.0 - below.0) as f64;
+            let size = below.1 as f64 + t * (above.1 as f64 - below.1 as f64);
+            let half_width = below.2 as f64 + t * (above.2 as f64 - below.2 as f64);
+            // Keep the half-width a multiple of 1024 so the interval splits evenly
+            return (size as usize, (half_width as usize).div_ceil(1024) * 1024);
+        }
+        below = *above;
+    }
+    (below.1, below.2)
+}
+
+/// State for all the polynomials that share a leading coefficient a. Switching between them only
+/// needs the roots adjusted by precomputed amounts, which is what makes the sieve self-initialising.
+struct PolynomialFamily {
+    a: BigUint,
+    /// Factor base indices of the primes whose product is a
+    a_indices: Vec<usize>,
+    /// B_l, whose signed sum is b
+    b_terms: Vec<BigUint>,
+    /// 2 B_l a^-1 mod p for each l and factor base prime p
+    b_steps: Vec<Vec<u32>>,
+    /// Whether each factor base prime divides a
+    divides_a: Vec<bool>,
+}
+
+/// Helper function, chooses a new leading coefficient a as a product of `s` factor base primes near
+/// `prime_target`, with the last chosen so that a is close to `target`. Returns `None` if no unused
+/// a can be found.
+fn _choose_a<R: RandomSource>(factor_base: &FactorBase, k: u32, s: usize, prime_target: f64, target: &BigUint,
+                              used: &mut HashSet<BigUint>, rng: &mut R) -> Option<(BigUint, Vec<usize>)> {
+    // Candidates are primes within a factor of two of the target that don't divide the multiplier
+    let mut candidates: Vec<usize> = (0..factor_base.primes.len())
+        .filter(|i| {
+            let p = factor_base.primes[*i];
+            !k.is_multiple_of(p) && p >= MIN_SIEVED_PRIME && (p as f64) > prime_target / 2.0 && (p as f64) < prime_target * 2.0
+        })
+        .collect();
+    if candidates.len() < s + 2 {
+        candidates = (0..factor_base.primes.len())
+            .filter(|i| !k.is_multiple_of(factor_base.primes[*i]) && factor_base.primes[*i] > 3)
+            .collect();
+    }
+    if candidates.len() < s + 1 {
+        return None;
+    }
+
+    for _ in 0..100 {
+        let mut indices: Vec<usize> = Vec::new();
+        while indices.len() < s - 1 {
+            let index = candidates[rng.gen_range(0, candidates.len() as u64) as usize];
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+        let partial: BigUint = indices.iter().map(|i| BigUint::from(factor_base.primes[*i])).product();
+
+        // The last prime is the candidate closest to what is left of the target
+        let wanted = (target / &partial).to_f64().unwrap_or(f64::MAX);
+        let last = candidates.iter()
+            .filter(|i| !indices.contains(i))
+            .min_by(|a, b| {
+                let distance = |i: usize| (factor_base.primes[i] as f64 - wanted).abs();
+                distance(**a).partial_cmp(&distance(**b)).unwrap()
+            })
+            .cloned()?;
+        indices.push(last);
+
+        let a = partial * factor_base.primes[last];
+        if used.insert(a.clone()) {
+            indices.sort_unstable();
+            return Some((a, indices));
+        }
+    }
+    None
+}
+
+/// Helper function, sets up the family of polynomials with leading coefficient `a`, returning it with
+/// the roots of the first polynomial as sieve positions
+fn _new_family(factor_base: &FactorBase, kn: &BigUint, a: BigUint, a_indices: Vec<usize>,
+               half_width: usize) -> (PolynomialFamily, BigInt, Vec<u32>, Vec<u32>) {
+    let primes = &factor_base.primes;
+    let mut divides_a = vec![false; primes.len()];
+    for i in &a_indices {
+        divides_a[*i] = true;
+    }
+
+    // B_l = (a / q_l) * gamma_l, where gamma_l = sqrt(kN) (a / q_l)^-1 mod q_l, so that b = sum of B_l has
+    // b^2 = kN mod a
+    let gammas: Vec<u64> = a_indices.iter()
+        .map(|i| {
+            let q = primes[*i] as u64;
+            let inverse = _inverse_mod(((&a / q) % q).to_u64().unwrap(), q);
+            let gamma = factor_base.roots[*i] as u64 * inverse % q;
+            gamma.min(q - gamma)
+        })
+        .collect();
+    let b_terms: Vec<BigUint> = a_indices.iter().zip(gammas.iter())
+        .map(|(i, gamma)| &a / primes[*i] * *gamma)
+        .collect();
+    let b: BigUint = b_terms.iter().sum();
+    debug_assert!(((&b * &b) % &a) == (kn % &a));
+
+    let mut b_steps = vec![vec![0_u32; primes.len()]; b_terms.len()];
+    let mut first = vec![0_u32; primes.len()];
+    let mut second = vec![0_u32; primes.len()];
+    for (i, p) in primes.iter().enumerate() {
+        if divides_a[i] {
+            continue;
+        }
+
+        // Everything is reduced mod p from the factors of a, which is much quicker than reducing a and
+        // each B_l directly
+        let p = *p as u64;
+        let q_mods: Vec<u64> = a_indices.iter().map(|j| primes[*j] as u64 % p).collect();
+        let a_inverse = _inverse_mod(q_mods.iter().fold(1, |product, q| product * q % p), p);
+        let mut b_mod = 0;
+        for (l, gamma) in gammas.iter().enumerate() {
+            let a_over_q = q_mods.iter().enumerate()
+                .filter(|(j, _)| *j != l)
+                .fold(1, |product, (_, q)| product * q % p);
+            let b_term = a_over_q * (gamma % p) % p;
+            b_mod = (b_mod + b_term) % p;
+            b_steps[l][i] = (2 * b_term * a_inverse % p) as u32;
+        }
+
+        // Roots of a x^2 + 2 b x + c are x = (+-sqrt(kN) - b) / a, shifted by M into sieve positions
+        let root = factor_base.roots[i] as u64;
+        let offset = half_width as u64 % p;
+        first[i] = (((root + p - b_mod) % p * a_inverse + offset) % p) as u32;
+        second[i] = (((2 * p - root - b_mod) % p * a_inverse + offset) % p) as u32;
+    }
+
+    let family = PolynomialFamily { a, a_indices, b_terms, b_steps, divides_a };
+    (family, BigInt::from_biguint(Sign::Plus, b), first, second)
+}
+
+/// Helper function, moves the sieve positions of the roots mod each prime on to the next polynomial
+/// in a family, where `b_steps` are 2 B_l a^-1 mod p for the B_l whose sign is flipped. Primes dividing
+/// a have steps of zero, so their (unused) positions stay put.
+fn _update_roots(first: &mut [u32], second: &mut [u32], primes: &[u32], b_steps: &[u32], negative: bool) {
+    let roots = first.iter_mut().zip(second.iter_mut());
+    for ((root_pair, p), b_step) in roots.zip(primes.iter()).zip(b_steps.iter()) {
+        let delta = if negative { *b_step } else { p - b_step };
+        // Reduce with min rather than a branch, which would be mispredicted half the time
+        let first = *root_pair.0 + delta;
+        *root_pair.0 = first.min(first.wrapping_sub(*p));
+        let second = *root_pair.1 + delta;
+        *root_pair.1 = second.min(second.wrapping_sub(*p));
+    }
+}
+
+/// Helper function, adds `log` to every `p`th value of `sieve` from `start`
+fn _sieve_root(sieve: &mut [u8], start: u32, p: usize, log: u8) {
+    let mut position = start as usize;
+    while position < sieve.len() {
+        sieve[position] = sieve[position].wrapping_add(log);
+        position += p;
+    }
+}
+
+/// Helper function, sieves the interval, adding log p at each position where p divides the
+/// polynomial's value, and returns the positions that reach `threshold`. Values start at
+/// 128 - `threshold`, so those that reach it are the ones with their top bit set. `first` and `second`
+/// are the positions of the two roots mod each prime.
+fn _sieve(factor_base: &FactorBase, divides_a: &[bool], first: &[u32], second: &[u32], threshold: u8,
+          sieve: &mut [u8]) -> Vec<usize> {
+    sieve.fill(128 - threshold);
+    for (j, p) in factor_base.primes.iter().enumerate() {
+        if *p < MIN_SIEVED_PRIME || divides_a[j] {
+            continue;
+        }
+        let (p, log) = (*p as usize, factor_base.logs[j]);
+        _sieve_root(sieve, first[j], p, log);
+        if first[j] != second[j] {
+            _sieve_root(sieve, second[j], p, log);
+        }
+    }
+
+    // Check eight values at a time for top bits
+    let mut hits = Vec::new();
+    for (chunk_index, chunk) in sieve.chunks_exact(8).enumerate() {
+        let word = u64::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5], chunk[6], chunk[7]]);
+        if word & 0x8080_8080_8080_8080 != 0 {
+            hits.extend((0..8).filter(|i| chunk[*i] & 0x80 != 0).map(|i| chunk_index * 8 + i));
+        }
+    }
+    hits
+}
+
+/// Helper function, factors the value of the polynomial at sieve position `position` over the factor
+/// base. Returns the relation and, for a partial relation, its large prime.
+#[allow(clippy::too_many_arguments)]
+fn _check_candidate(n: &BigUint, factor_base: &FactorBase, family: &PolynomialFamily, b: &BigInt, c: &BigInt,
+                    first: &[u32], second: &[u32], position: usize, half_width: usize,
+                    large_prime_bound: u64) -> Option<(Relation, u64)> {
+    let x = BigInt::from(position as i64 - half_width as i64);
+    let a = BigInt::from_biguint(Sign::Plus, family.a.clone());
+    let u = &a * &x + b;
+    let value: BigInt = (&a * &x + b * 2) * &x + c;
+    if value.is_zero() {
+        return None;
+    }
+
+    // a g(x) = u^2 - kN, so a's primes are factors too
+    let mut factors: Vec<usize> = family.a_indices.iter().map(|i| i + 2).collect();
+    if value.is_negative() {
+        factors.push(0);
+    }
+    let mut rest = value.abs().to_biguint().unwrap();
+    while rest.is_even() {
+        rest >>= 1;
+        factors.push(1);
+    }
+
+    for (i, p) in factor_base.primes.iter().enumerate() {
+        let hit = if family.divides_a[i] {
+            (&rest % *p).is_zero()
+        } else {
+            let position = position as u32;
+            let r = if position < *p { position } else { _fast_mod(position, *p, factor_base.reciprocals[i]) };
+            r == first[i] || r == second[i]
+        };
+        if hit {
+            loop {
+                let (quotient, remainder) = rest.div_rem(&BigUint::from(*p));
+                if !remainder.is_zero() {
+                    break;
+                }
+                rest = quotient;
+                factors.push(i + 2);
+            }
+        }
+    }
+
+    let large_prime = if rest.is_one() {
+        1
+    } else {
+        match rest.to_u64() {
+            Some(rest) if rest < large_prime_bound && rest > *factor_base.primes.last().unwrap() as u64 => rest,
+            _ => return None,
+        }
+    };
+    let u = u.mod_floor(&BigInt::from_biguint(Sign::Plus, n.clone())).to_biguint().unwrap();
+    Some((Relation { u, factors, square: One::one() }, large_prime))
+}
+
+/// Helper function, finds sets of relations whose products are squares by Gaussian elimination over
+/// GF(2) on the parities of their exponents. Returns each set as a list of indices into `relations`.
+fn _find_dependencies(relations: &[Relation], columns: usize) -> Vec<Vec<usize>> {
+    let rows = relations.len();
+    let matrix_words = columns.div_ceil(64);
+    let words = matrix_words + rows.div_ceil(64);
+
+    // Each row holds the exponent parities followed by which relations have been added into it
+    let mut matrix: Vec<Vec<u64>> = relations.iter().enumerate()
+        .map(|(i, relation)| {
+            let mut row = vec![0_u64; words];
+            for column in &relation.factors {
+                row[column / 64] ^= 1 << (column % 64);
+            }
+            row[matrix_words + i / 64] |= 1 << (i % 64);
+            row
+        })
+        .collect();
+
+    let mut used = vec![false; rows];
+    for column in 0..columns {
+        let (word, bit) = (column / 64, 1_u64 << (column % 64));
+        let pivot = match (0..rows).find(|r| !used[*r] && matrix[*r][word] & bit != 0) {
+            Some(pivot) => pivot,
+            None => continue,
+        };
+        used[pivot] = true;
+
+        // The pivot row has no bits left in earlier columns, so only later words need adding
+        let pivot_row = matrix[pivot].clone();
+        for (r, row) in matrix.iter_mut().enumerate() {
+            if !used[r] && row[word] & bit != 0 {
+                for (target, source) in row[word..].iter_mut().zip(pivot_row[word..].iter()) {
+                    *target ^= source;
+                }
+            }
+        }
+    }
+
+    // Rows never used as pivots have been reduced to zero, so the relations added into them multiply
+    // to a square
+    (0..rows).filter(|r| !used[*r])
+        .map(|r| (0..rows).filter(|i| matrix[r][matrix_words + i / 64] & (1 << (i % 64)) != 0).collect())
+        .collect()
+}
+
+/// Helper function, tries each dependency in turn, returning a factor of `n` from the first one whose
+/// square roots x and y are not x = +-y mod n
+fn _square_root(n: &BigUint, factor_base: &FactorBase, relations: &[Relation],
+                dependencies: &[Vec<usize>]) -> Option<BigUint> {
+    let columns = factor_base.primes.len() + 2;
+    for dependency in dependencies {
+        let mut x: BigUint = One::one();
+        let mut y: BigUint = One::one();
+        let mut exponents = vec![0_u32; columns];
+        for i in dependency {
+            let relation = &relations[*i];
+            x = x * &relation.u % n;
+            y = y * &relation.square % n;
+            for column in &relation.factors {
+                exponents[*column] += 1;
+            }
+        }
+        for (column, exponent) in exponents.iter().enumerate().skip(1) {
+            debug_assert!(exponent % 2 == 0);
+            if *exponent > 0 {
+                let p = BigUint::from(factor_base.prime(column));
+                y = y * p.modpow(&BigUint::from(exponent / 2), n) % n;
+            }
+        }
+
+        let g = ((&x + n - &y) % n).gcd(n);
+        if !g.is_one() && g != *n {
+            return Some(g);
+        }
+    }
+    None
+}
+
+/// Returns a factor of `n` found by the self-initialising quadratic sieve. Relations
+/// (ax + b)^2 = a g(x) (mod n) are collected by sieving polynomials g(x) = ax^2 + 2bx + c for values
+/// that factor over a base of small primes, allowing one larger prime; then Gaussian elimination finds
+/// sets of relations multiplying to congruent squares x^2 = y^2 (mod n), and gcd(x - y, n) is a factor.
+/// Unlike ECM, the running time depends only on the size of `n`, so this is the method for numbers
+/// whose factors are all big, like RSA moduli. Gives up after `budget`, or if `n` is outside
+/// `MIN_SIEVE_BITS` to `MAX_SIEVE_BITS`, prime or a perfect power.
+///
+/// # Arguments
+/// * `n` - Composite number to factor
+/// * `budget` - Time allowed
+/// * `rng` - Random number generator for choosing polynomials
+/// * `progress` - Called with the relations collected after each batch of polynomials
+pub fn quadratic_sieve<R, F>(n: &BigUint, budget: Duration, rng: &mut R, mut progress: F) -> Option<BigUint>
+    where R: RandomSource, F: FnMut(&SieveProgress) {
+    let start = Instant::now();
+    let bits = n.bits();
+    if !(MIN_SIEVE_BITS..=MAX_SIEVE_BITS).contains(&bits) || factor::perfect_power(n).is_some() {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigUint::from(2_u32));
+    }
+
+    let k = _choose_multiplier(n);
+    let kn = n * k;
+    let (size, half_width) = _parameters(bits);
+    let factor_base = match _factor_base(n, &kn, size) {
+        Ok(factor_base) => factor_base,
+        Err(factor) => return Some(factor),
+    };
+    let largest_prime = *factor_base.primes.last().unwrap() as u64;
+    let large_prime_bound = largest_prime * LARGE_PRIME_MULTIPLIER;
+    let columns = factor_base.primes.len() + 2;
+    let target = columns + EXTRA_RELATIONS;
+
+    // a is about sqrt(2kN) / M, which makes |g(x)| about M sqrt(kN / 2) at most across the interval
+    let a_target = (&kn << 1).sqrt() / half_width;
+    let a_bits = a_target.bits() as f64;
+    let max_prime_bits = (largest_prime as f64).log2() - 1.0;
+    let s = ((a_bits / max_prime_bits.min(11.0)).ceil() as usize).max(1);
+    let prime_target = 2_f64.powf(a_bits / s as f64);
+
+    // Sieve values at least this are worth trial dividing. Small numbers have so few bits to spare that
+    // the slack would let every position through, so at least half of the value must be sieved out.
+    let max_value_bits = (half_width as f64).log2() + (kn.bits() as f64) / 2.0 - 0.5;
+    let threshold_bits = (max_value_bits - (large_prime_bound as f64).log2() - THRESHOLD_SLACK).max(max_value_bits / 2.0);
+    let scale = (MAX_THRESHOLD as f64 / threshold_bits).min(1.0);
+    let threshold = (threshold_bits * scale) as u8;
+    let mut factor_base = factor_base;
+    factor_base.logs = factor_base.primes.iter().map(|p| ((*p as f64).log2() * scale).round() as u8).collect();
+
+    let mut sieve = vec![0_u8; 2 * half_width];
+    let mut used = HashSet::new();
+    let mut relations: Vec<Relation> = Vec::new();
+    let mut partials: HashMap<u64, Relation> = HashMap::new();
+    let mut report = SieveProgress {
+        elapsed: Duration::default(),
+        polynomials: 0,
+        full_relations: 0,
+        combined_relations: 0,
+        partial_relations: 0,
+        target,
+    };
+
+    while relations.len() < target {
+        if start.elapsed() > budget {
+            return None;
+        }
+        let (a, a_indices) = _choose_a(&factor_base, k, s, prime_target, &a_target, &mut used, rng)?;
+        let (family, mut b, mut first, mut second) = _new_family(&factor_base, &kn, a, a_indices, half_width);
+        let a_signed = BigInt::from_biguint(Sign::Plus, family.a.clone());
+        let kn_signed = BigInt::from_biguint(Sign::Plus, kn.clone());
+
+        // Step through the 2^(s-1) choices of sign for B_1.. in Gray code order, so each step flips one
+        let polynomials = 1_u64 << (family.b_terms.len() - 1);
+        for i in 0..polynomials {
+            if i > 0 {
+                let l = i.trailing_zeros() as usize + 1;
+                let negative = ((i ^ (i >> 1)) >> (l - 1)) & 1 == 1;
+                let step = BigInt::from_biguint(Sign::Plus, &family.b_terms[l] << 1);
+                _update_roots(&mut first, &mut second, &factor_base.primes, &family.b_steps[l], negative);
+                if negative { b -= step } else { b += step }
+            }
+            let c = (&b * &b - &kn_signed) / &a_signed;
+
+            let hits = _sieve(&factor_base, &family.divides_a, &first, &second, threshold, &mut sieve);
+            for position in hits {
+                let candidate = _check_candidate(n, &factor_base, &family, &b, &c, &first, &second, position,
+                                                 half_width, large_prime_bound);
+                match candidate {
+                    Some((relation, 1)) => {
+                        relations.push(relation);
+                        report.full_relations += 1;
+                    },
+                    Some((relation, large_prime)) => {
+                        report.partial_relations += 1;
+                        match partials.get(&large_prime) {
+                            Some(other) => {
+                                let mut factors = other.factors.clone();
+                                factors.extend(relation.factors);
+                                relations.push(Relation {
+                                    u: &other.u * relation.u % n,
+                                    factors,
+                                    square: BigUint::from(large_prime),
+                                });
+                                report.combined_relations += 1;
+                            },
+                            None => {
+                                partials.insert(large_prime, relation);
+                            },
+                        }
+                    },
+                    None => {},
+                }
+            }
+            report.polynomials += 1;
+        }
+
+        report.elapsed = start.elapsed();
+        progress(&report);
+    }
+
+    relations.truncate(target);
+    let dependencies = _find_dependencies(&relations, columns);
+    _square_root(n, &factor_base, &relations, &dependencies)
+}
@@ -0,0 +1,255 @@
+use std::io;
+use std::io::{Read, Write};
+
+use crate::aead;
+use crate::hybrid;
+use crate::hybrid::{Header, KeyWrap};
+use crate::rand::RandomSource;
+use crate::rsa::{Error, RsaPrivateKey, RsaPublicKey};
+
+/// Number of plaintext bytes in every chunk but the last, which holds between 0 and this many
+pub const CHUNK_SIZE: usize = 1 << 16;
+
+/// Helper function, returns the nonce for chunk number `counter`: the nonce from the header with
+/// the counter XORed into bytes 7 to 10 and the last-chunk flag into byte 11. Every chunk has its own
+/// nonce, so chunks can't be reordered, and the flag means a stream cut short at a chunk boundary
+/// fails to decrypt instead of looking complete.
+fn _chunk_nonce(base: &[u8; aead::NONCE_SIZE], counter: u32, last: bool) -> [u8; aead::NONCE_SIZE] {
+    let mut nonce = *base;
+    for (byte, counter_byte) in nonce[7..11].iter_mut().zip(counter.to_be_bytes().iter()) {
+        *byte ^= counter_byte;
+    }
+    nonce[11] ^= last as u8;
+    nonce
+}
+
+/// Helper function, converts an error of this crate to an I/O error
+pub(crate) fn _io_error(error: Error) -> io::Error {
+    let kind = match error {
+        Error::MessageTooLong | Error::KeyTooSmall | Error::InvalidParameters => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::InvalidData,
+    };
+    io::Error::new(kind, error)
+}
+
+/// Encrypts everything written to it under a public key, writing a container to the underlying
+/// writer as it goes. The header is the same as for `hybrid::encrypt`, but the message is sealed
+/// with ChaCha20-Poly1305 in chunks of `CHUNK_SIZE` bytes (the STREAM construction of Hoang et al.),
+/// so memory use stays the same however long the message is. `finish` must be called to write the
+/// last chunk; a container without it fails to decrypt.
+pub struct Encryptor<W: Write> {
+    /// Where the container is written
+    inner: W,
+    /// Content key
+    key: [u8; aead::KEY_SIZE],
+    /// Nonce from the header, from which each chunk's nonce is derived
+    nonce: [u8; aead::NONCE_SIZE],
+    /// The header, authenticated with every chunk
+    header: Vec<u8>,
+    /// Plaintext not yet sealed, at most `CHUNK_SIZE` bytes between writes
+    buffer: Vec<u8>,
+    /// Number of the next chunk
+    counter: u32,
+}
+
+impl<W: Write> Encryptor<W> {
+    /// Returns a new encryptor, having written the header to `inner`
+    ///
+    /// # Arguments
+    /// * `inner` - Where to write the container
+    /// * `pubkey` - Public key of the recipient
+    /// * `wrap` - How to wrap the content key
+    /// * `rng` - Random number generator for the content key, nonce and padding
+    pub fn new<R: RandomSource>(inner: W, pubkey: &RsaPublicKey, wrap: KeyWrap,
+                                rng: &mut R) -> io::Result<Encryptor<W>> {
+        let (key, wrapped_key) = hybrid::_encapsulate(pubkey, wrap, rng).map_err(_io_error)?;
+        let mut nonce = [0_u8; aead::NONCE_SIZE];
+        rng.fill_bytes(&mut nonce);
+
+        let header = hybrid::_write_header(&Header { version: hybrid::STREAM_VERSION, wrap, wrapped_key, nonce });
+        Encryptor::_with_header(inner, key, nonce, header)
+    }
+
+    /// Helper function, returns a new encryptor for content key `key` and header nonce `nonce`,
+    /// having written `header` to `inner`
+    pub(crate) fn _with_header(mut inner: W, key: [u8; aead::KEY_SIZE], nonce: [u8; aead::NONCE_SIZE],
+                               header: Vec<u8>) -> io::Result<Encryptor<W>> {
+        inner.write_all(&header)?;
+        Ok(Encryptor { inner, key, nonce, header, buffer: Vec::with_capacity(CHUNK_SIZE + 1), counter: 0 })
+    }
+
+    /// Helper function, seals up to `CHUNK_SIZE` bytes from the start of the buffer as the next chunk
+    fn _write_chunk(&mut self, last: bool) -> io::Result<()> {
+        let len = self.buffer.len().min(CHUNK_SIZE);
+        let nonce = _chunk_nonce(&self.nonce, self.counter, last);
+        let sealed = aead::seal(&self.key, &nonce, &self.header, &self.buffer[..len]);
+        self.inner.write_all(&sealed)?;
+        self.buffer.drain(..len);
+        self.counter = self.counter.checked_add(1)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "stream has too many chunks"))?;
+        Ok(())
+    }
+
+    /// Writes the last chunk and flushes, returning the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self._write_chunk(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Encryptor<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // A full chunk is only sealed once a byte after it arrives, since until then it might be the
+        // last one
+        let taken = data.len().min(CHUNK_SIZE + 1 - self.buffer.len());
+        self.buffer.extend_from_slice(&data[..taken]);
+        if self.buffer.len() > CHUNK_SIZE {
+            self._write_chunk(false)?;
+        }
+        Ok(taken)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a container written by `Encryptor` as it is read from the underlying reader, holding
+/// at most two chunks in memory. Each chunk is checked before any of it is returned, so altered,
+/// reordered or missing data gives an `InvalidData` error. Everything read before such an error is
+/// genuine, but the message is only known to be complete once the reader returns 0.
+pub struct Decryptor<R: Read> {
+    /// Where the container is read from
+    inner: R,
+    /// Content key
+    key: [u8; aead::KEY_SIZE],
+    /// Nonce from the header, from which each chunk's nonce is derived
+    nonce: [u8; aead::NONCE_SIZE],
+    /// The header, authenticated with every chunk
+    header: Vec<u8>,
+    /// Sealed bytes read ahead of the current chunk
+    pending: Vec<u8>,
+    /// The current chunk, decrypted
+    plaintext: Vec<u8>,
+    /// Number of bytes of `plaintext` already returned
+    position: usize,
+    /// Number of the next chunk
+    counter: u32,
+    /// Whether the last chunk has been decrypted
+    finished: bool,
+}
+
+impl<R: Read> Decryptor<R> {
+    /// Returns a new decryptor, having read the header from `inner` and unwrapped the content key.
+    /// Fails with `InvalidData` if it isn't a streamed container or was encrypted for another key.
+    ///
+    /// # Arguments
+    /// * `inner` - Where to read the container from
+    /// * `privkey` - The private key to use for decryption
+    pub fn new(mut inner: R, privkey: &RsaPrivateKey) -> io::Result<Decryptor<R>> {
+        let mut header = vec![0_u8; hybrid::FIXED_HEADER_SIZE];
+        inner.read_exact(&mut header)?;
+        let wrapped_len = u16::from_be_bytes([header[7], header[8]]) as usize;
+        header.resize(hybrid::FIXED_HEADER_SIZE + wrapped_len + aead::NONCE_SIZE, 0);
+        inner.read_exact(&mut header[hybrid::FIXED_HEADER_SIZE..])?;
+
+        let (parsed, _) = hybrid::_read_header(&header).map_err(_io_error)?;
+        if parsed.version != hybrid::STREAM_VERSION {
+            return Err(_io_error(Error::Decryption));
+        }
+        let key = hybrid::_decapsulate(privkey, parsed.wrap, &parsed.wrapped_key).map_err(_io_error)?;
+        Ok(Decryptor::_with_header(inner, key, parsed.nonce, header))
+    }
+
+    /// Helper function, returns a new decryptor for content key `key` and header nonce `nonce`, with
+    /// `header` already read from `inner`
+    pub(crate) fn _with_header(inner: R, key: [u8; aead::KEY_SIZE], nonce: [u8; aead::NONCE_SIZE],
+                               header: Vec<u8>) -> Decryptor<R> {
+        Decryptor {
+            inner,
+            key,
+            nonce,
+            header,
+            pending: Vec::with_capacity(CHUNK_SIZE + aead::TAG_SIZE + 1),
+            plaintext: Vec::new(),
+            position: 0,
+            counter: 0,
+            finished: false,
+        }
+    }
+
+    /// Helper function, reads and decrypts the next chunk. One byte past a full chunk is read ahead
+    /// to tell whether it is the last.
+    fn _next_chunk(&mut self) -> io::Result<()> {
+        let wanted = CHUNK_SIZE + aead::TAG_SIZE + 1;
+        while self.pending.len() < wanted {
+            let start = self.pending.len();
+            self.pending.resize(wanted, 0);
+            let read = self.inner.read(&mut self.pending[start..]);
+            self.pending.truncate(start + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => break,
+                Ok(_) => {},
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+
+        let last = self.pending.len() < wanted;
+        let len = self.pending.len().min(CHUNK_SIZE + aead::TAG_SIZE);
+        let nonce = _chunk_nonce(&self.nonce, self.counter, last);
+        self.plaintext = aead::open(&self.key, &nonce, &self.header, &self.pending[..len]).map_err(_io_error)?;
+        self.pending.drain(..len);
+        self.position = 0;
+        self.counter = self.counter.wrapping_add(1);
+        self.finished = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self._next_chunk()?;
+        }
+
+        let len = buf.len().min(self.plaintext.len() - self.position);
+        buf[..len].copy_from_slice(&self.plaintext[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Encrypts everything read from `input` under `pubkey` with an `Encryptor`, writing the container
+/// to `output`
+///
+/// # Arguments
+/// * `input` - Where to read the message from
+/// * `output` - Where to write the container
+/// * `pubkey` - Public key of the recipient
+/// * `wrap` - How to wrap the content key
+/// * `rng` - Random number generator for the content key, nonce and padding
+pub fn encrypt_stream<I: Read, O: Write, R: RandomSource>(mut input: I, output: O, pubkey: &RsaPublicKey,
+                                                          wrap: KeyWrap, rng: &mut R) -> io::Result<()> {
+    let mut encryptor = Encryptor::new(output, pubkey, wrap, rng)?;
+    io::copy(&mut input, &mut encryptor)?;
+    encryptor.finish()?;
+    Ok(())
+}
+
+/// Decrypts a container read from `input` with a `Decryptor`, writing the message to `output`. On
+/// error, what was written to `output` is genuine but incomplete.
+///
+/// # Arguments
+/// * `input` - Where to read the container from
+/// * `output` - Where to write the message
+/// * `privkey` - The private key to use for decryption
+pub fn decrypt_stream<I: Read, O: Write>(input: I, mut output: O, privkey: &RsaPrivateKey) -> io::Result<()> {
+    let mut decryptor = Decryptor::new(input, privkey)?;
+    io::copy(&mut decryptor, &mut output)?;
+    output.flush()
+}
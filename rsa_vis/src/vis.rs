@@ -1,18 +1,61 @@
-use crate::rand;
+use crate::rand::RandomSource;
+use crate::siqs::SieveProgress;
 use bmp::{Image, Pixel, px};
 
-/// Return a bitmap visualization of numbers generated by rsa_vis::rng.
+/// Return a bitmap visualization of numbers generated by `rng`. Each pixel is black or white at
+/// random, so patterns in the image point to patterns in the generator.
 ///
 /// # Arguments
 ///
 /// * `img_size` - A u32 specifying size of square image to be created.
-pub fn generate_rng_bitmap(img_size: u32) -> Image {
+/// * `rng` - The random number generator to visualize
+pub fn generate_rng_bitmap<R: RandomSource + ?Sized>(img_size: u32, rng: &mut R) -> Image {
     let mut img = Image::new(img_size, img_size);
-    let mut rng = rand::new();
 
     for (x, y) in img.coordinates() {
-        let res = if rng.next_int(0, 2) == 0 { 0 } else { 255 };
+        let res = if rng.gen_range(0, 2) == 0 { 0 } else { 255 };
         img.set_pixel(x, y, px!(res, res, res));
     }
-    return img;
-}
\ No newline at end of file
+    img
+}
+
+/// Return a chart of the relations collected by the quadratic sieve over time, from the reports it
+/// made along the way. Time runs left to right; usable relations are drawn in black and full relations
+/// in blue, up to the red line at the number needed. Partial relations pair up more and more often as
+/// they pile up, which is why the black line curves upwards.
+///
+/// # Arguments
+///
+/// * `history` - Progress reports from `siqs::quadratic_sieve`, in order
+/// * `img_width` - Width of the image in pixels
+/// * `img_height` - Height of the image in pixels
+pub fn relations_chart(history: &[SieveProgress], img_width: u32, img_height: u32) -> Image {
+    let mut img = Image::new(img_width, img_height);
+    for (x, y) in img.coordinates() {
+        img.set_pixel(x, y, px!(255, 255, 255));
+    }
+    let last = match history.last() {
+        Some(last) => last,
+        None => return img,
+    };
+
+    // Leave a tenth of the height above the target line
+    let top = last.target.max(last.relations()) as f64 * 1.1;
+    let row = |relations: usize| {
+        let height = (relations as f64 / top * img_height as f64) as u32;
+        img_height - 1 - height.min(img_height - 1)
+    };
+    let duration = last.elapsed.as_secs_f64().max(1e-9);
+
+    for x in 0..img_width {
+        img.set_pixel(x, row(last.target), px!(255, 0, 0));
+
+        // The latest report at or before this column's time
+        let time = duration * (x + 1) as f64 / img_width as f64;
+        if let Some(report) = history.iter().take_while(|r| r.elapsed.as_secs_f64() <= time).last() {
+            img.set_pixel(x, row(report.full_relations), px!(0, 0, 255));
+            img.set_pixel(x, row(report.relations()), px!(0, 0, 0));
+        }
+    }
+    img
+}
@@ -0,0 +1,107 @@
+extern crate num;
+extern crate rsa_vis;
+
+mod common;
+
+use num::{BigInt, BigUint, Integer};
+use num::bigint::Sign;
+use num::traits::One;
+
+use rsa_vis::attacks;
+use rsa_vis::rsa::{self, RsaPrivateKey, RsaPublicKey};
+
+use common::{key, key_with_exponent};
+
+const MESSAGE: &str = "attack at dawn";
+
+/// Checks that `recovered` decrypts what is encrypted under `public`
+fn assert_works_for(recovered: &RsaPrivateKey, public: &RsaPublicKey) {
+    assert_eq!(recovered.n, public.n);
+    let cipher = rsa::encrypt_str(MESSAGE, public).unwrap();
+    assert_eq!(rsa::decrypt_str(&cipher, recovered).unwrap(), MESSAGE);
+}
+
+#[test]
+fn wiener_recovers_small_private_exponent() {
+    let base = key(512, 1);
+    let (p, q) = (base.p.clone().unwrap(), base.q.clone().unwrap());
+    let one: BigUint = One::one();
+    let lambda = (&p - &one).lcm(&(&q - &one));
+
+    // An odd d of about 100 bits, well below n^(1/4) / 3, with e its inverse mod lambda(n)
+    let mut d = (BigUint::one() << 100) + 1_u32;
+    while !d.gcd(&lambda).is_one() {
+        d += 2_u32;
+    }
+    let lambda_int = BigInt::from_biguint(Sign::Plus, lambda.clone());
+    let inverse = BigInt::from_biguint(Sign::Plus, d.clone()).extended_gcd(&lambda_int).x;
+    let e = inverse.mod_floor(&lambda_int).to_biguint().unwrap();
+
+    let weak = RsaPublicKey::new(base.n.clone(), e);
+    let recovered = attacks::wiener(&weak).expect("d is small enough for Wiener's attack");
+    assert_eq!(recovered.d, d);
+    assert_works_for(&recovered, &weak);
+}
+
+#[test]
+fn wiener_fails_on_normal_key() {
+    assert!(attacks::wiener(&key(512, 2).to_public_key()).is_none());
+}
+
+#[test]
+fn hastad_recovers_broadcast_message() {
+    let keys: Vec<RsaPublicKey> = (3..6).map(|seed| key_with_exponent(512, 3, seed).to_public_key()).collect();
+    let ciphers: Vec<Vec<BigUint>> = keys.iter().map(|key| rsa::encrypt_str(MESSAGE, key).unwrap()).collect();
+
+    assert_eq!(attacks::hastad_broadcast(&keys, &ciphers).as_deref(), Some(MESSAGE));
+    assert_eq!(attacks::hastad_broadcast(&keys[..2], &ciphers[..2]), None);
+}
+
+#[test]
+fn hastad_rejects_empty_input() {
+    assert_eq!(attacks::hastad_broadcast(&[], &[vec![]]), None);
+    assert_eq!(attacks::hastad_broadcast(&[], &[]), None);
+    assert_eq!(attacks::hastad_broadcast_block(&[], &[]), None);
+}
+
+#[test]
+fn hastad_rejects_exponents_below_two() {
+    let n = key(64, 6).n;
+    for e in 0..2_u32 {
+        let keys = vec![RsaPublicKey::new(n.clone(), BigUint::from(e)); 3];
+        let ciphers = vec![BigUint::from(5_u32); 3];
+        assert_eq!(attacks::hastad_broadcast_block(&keys, &ciphers), None);
+    }
+}
+
+#[test]
+fn common_modulus_recovers_message() {
+    let n = key(512, 7).n;
+    let key_one = RsaPublicKey::new(n.clone(), BigUint::from(65_537_u32));
+    let key_two = RsaPublicKey::new(n, BigUint::from(17_u32));
+    let cipher_one = rsa::encrypt_str(MESSAGE, &key_one).unwrap();
+    let cipher_two = rsa::encrypt_str(MESSAGE, &key_two).unwrap();
+
+    assert_eq!(attacks::common_modulus(&key_one, &key_two, &cipher_one, &cipher_two).as_deref(), Some(MESSAGE));
+}
+
+#[test]
+fn shared_factors_splits_keys_sharing_a_prime() {
+    let (one, two, other) = (key(512, 8), key(512, 9), key(512, 10));
+    let p = one.p.clone().unwrap();
+    let keys = vec![
+        one.to_public_key(),
+        RsaPublicKey::new(&p * two.q.as_ref().unwrap(), BigUint::from(65_537_u32)),
+        other.to_public_key(),
+    ];
+
+    let recovered = attacks::shared_factors(&keys);
+    assert_eq!(recovered.len(), 3);
+    for (key, private_key) in keys.iter().zip(&recovered).take(2) {
+        let private_key = private_key.as_ref().expect("the first two keys share p");
+        assert!(private_key.p.as_ref() == Some(&p) || private_key.q.as_ref() == Some(&p));
+        assert_works_for(private_key, key);
+    }
+    assert!(recovered[2].is_none());
+    assert!(attacks::shared_factors(&[]).is_empty());
+}
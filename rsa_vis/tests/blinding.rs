@@ -0,0 +1,38 @@
+extern crate num;
+extern crate rsa_vis;
+
+mod common;
+
+use num::BigUint;
+
+use rsa_vis::rand::RandomSource;
+use rsa_vis::rsa::{self, Blinding, RsaPrivateKey};
+
+use common::key;
+
+const BLINDINGS: [Blinding; 3] = [Blinding::None, Blinding::Base, Blinding::BaseAndExponent];
+
+#[test]
+fn crt_and_non_crt_decryption_agree() {
+    let mut rng = common::rng(1);
+    for (bits, seed) in [(64, 2), (512, 3), (1024, 4)].iter() {
+        let crt_key = key(*bits, *seed);
+        let plain_key = RsaPrivateKey::new(crt_key.n.clone(), crt_key.e.clone(), crt_key.d.clone(), None, None);
+        assert!(crt_key.crt.is_some() && plain_key.crt.is_none());
+
+        for _ in 0..4 {
+            let mut bytes = vec![0_u8; crt_key.size()];
+            rng.fill_bytes(&mut bytes);
+            let m = BigUint::from_bytes_be(&bytes) % &crt_key.n;
+            let c = rsa::rsaep(&m, &crt_key.to_public_key());
+
+            for blinding in BLINDINGS.iter() {
+                let crt_key = crt_key.clone().with_blinding(*blinding);
+                let plain_key = plain_key.clone().with_blinding(*blinding);
+                assert_eq!(rsa::rsadp(&c, &crt_key), m, "CRT, {:?}, {} bits", blinding, bits);
+                assert_eq!(rsa::rsadp(&c, &plain_key), m, "non-CRT, {:?}, {} bits", blinding, bits);
+                assert_eq!(rsa::rsadp_with_rng(&c, &crt_key, &mut rng), rsa::rsadp_with_rng(&c, &plain_key, &mut rng));
+            }
+        }
+    }
+}
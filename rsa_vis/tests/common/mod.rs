@@ -0,0 +1,33 @@
+//! Fixtures shared by the integration tests. Each test file uses only some of them.
+#![allow(dead_code)]
+
+use rsa_vis::rand::ChaChaRng;
+use rsa_vis::rsa::{KeyBuilder, RsaPrivateKey};
+
+/// Returns a generator seeded with `seed`, so that tests repeat from run to run
+pub fn rng(seed: u8) -> ChaChaRng {
+    ChaChaRng::from_seed([seed; 32])
+}
+
+/// Returns a key of `bits` bits with the default public exponent, from a generator seeded with `seed`
+pub fn key(bits: usize, seed: u8) -> RsaPrivateKey {
+    KeyBuilder::new().bits(bits).build(&mut rng(seed)).unwrap()
+}
+
+/// Returns a key of `bits` bits with public exponent `e`, from a generator seeded with `seed`
+pub fn key_with_exponent(bits: usize, e: u32, seed: u8) -> RsaPrivateKey {
+    KeyBuilder::new().bits(bits).exponent(e.into()).build(&mut rng(seed)).unwrap()
+}
+
+/// Returns a 1024-bit key and the generator it was made with, seeded with `seed`, to go on drawing
+/// padding or salts from
+pub fn key_and_rng(seed: u8) -> (RsaPrivateKey, ChaChaRng) {
+    let mut rng = rng(seed);
+    let key = KeyBuilder::new().bits(1024).build(&mut rng).unwrap();
+    (key, rng)
+}
+
+/// Returns `len` bytes of a message
+pub fn message(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
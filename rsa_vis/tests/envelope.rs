@@ -0,0 +1,65 @@
+extern crate rsa_vis;
+
+mod common;
+
+use rsa_vis::envelope::{self, KEY_ID_SIZE};
+use rsa_vis::hash::HashAlgorithm;
+use rsa_vis::hybrid::KeyWrap;
+use rsa_vis::rsa::{Error, RsaPrivateKey, RsaPublicKey};
+
+use common::{key, message};
+
+const WRAPS: [KeyWrap; 2] = [KeyWrap::Kem(HashAlgorithm::Sha256), KeyWrap::Oaep(HashAlgorithm::Sha256)];
+
+/// Offset of the first recipient slot's wrapped key: magic, version, key wrap, hash and number of
+/// recipients, then the slot's key ID and wrapped key length
+const FIRST_WRAPPED_KEY: usize = 9 + KEY_ID_SIZE + 2;
+
+/// Returns the public keys of `keys`
+fn public_keys(keys: &[RsaPrivateKey]) -> Vec<RsaPublicKey> {
+    keys.iter().map(RsaPrivateKey::to_public_key).collect()
+}
+
+#[test]
+fn every_recipient_can_decrypt() {
+    let keys: Vec<RsaPrivateKey> = (1..4).map(|seed| key(1024, seed)).collect();
+    let mut rng = common::rng(4);
+    for wrap in WRAPS.iter() {
+        let msg = message(1000);
+        let sealed = envelope::encrypt(&msg, &public_keys(&keys), *wrap, &mut rng).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(envelope::decrypt(&sealed, key).as_ref(), Ok(&msg), "{:?}, recipient {}", wrap, i);
+        }
+    }
+}
+
+#[test]
+fn non_recipient_is_rejected() {
+    let keys: Vec<RsaPrivateKey> = (5..7).map(|seed| key(1024, seed)).collect();
+    let outsider = key(1024, 7);
+    let mut rng = common::rng(8);
+    for wrap in WRAPS.iter() {
+        let sealed = envelope::encrypt(&message(100), &public_keys(&keys), *wrap, &mut rng).unwrap();
+        assert_eq!(envelope::decrypt(&sealed, &outsider), Err(Error::NotRecipient), "{:?}", wrap);
+    }
+}
+
+#[test]
+fn altered_slot_of_another_recipient_is_rejected() {
+    let keys: Vec<RsaPrivateKey> = (9..11).map(|seed| key(1024, seed)).collect();
+    let mut rng = common::rng(11);
+    for wrap in WRAPS.iter() {
+        let mut sealed = envelope::encrypt(&message(100), &public_keys(&keys), *wrap, &mut rng).unwrap();
+        assert_eq!(sealed[9..9 + KEY_ID_SIZE], envelope::key_id(&keys[0].to_public_key()));
+
+        // The second recipient never unwraps the first slot, but the whole header is authenticated
+        sealed[FIRST_WRAPPED_KEY] ^= 1;
+        assert_eq!(envelope::decrypt(&sealed, &keys[1]), Err(Error::Decryption), "{:?}", wrap);
+    }
+}
+
+#[test]
+fn no_recipients_is_rejected() {
+    let mut rng = common::rng(12);
+    assert_eq!(envelope::encrypt(&message(10), &[], KeyWrap::default(), &mut rng), Err(Error::InvalidParameters));
+}
@@ -0,0 +1,181 @@
+extern crate num;
+extern crate rsa_vis;
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use num::{BigUint, ToPrimitive};
+
+use rsa_vis::ecm;
+use rsa_vis::factor::{self, Factorisation, Method, Progress};
+use rsa_vis::primes::{self, PrimalityTest};
+use rsa_vis::siqs;
+
+/// Time allowed for each factorisation, far more than any of them need
+const BUDGET: Duration = Duration::from_secs(30);
+
+/// Returns a `bits`-bit prime from a generator seeded with `seed`
+fn prime(bits: usize, seed: u8) -> BigUint {
+    primes::gen_prime(bits, PrimalityTest::BailliePsw, &mut common::rng(seed))
+}
+
+/// Returns the primes of `factorisation`, checking that it is complete
+fn complete_factors(factorisation: &Factorisation) -> Vec<BigUint> {
+    assert!(factorisation.is_complete(), "left {:?} unfactored", factorisation.unfactored);
+    factorisation.factors.iter().map(|factor| factor.prime.clone()).collect()
+}
+
+#[test]
+fn prime_powers_are_factored() {
+    let (p, q) = (prime(40, 1), prime(40, 2));
+    let mut rng = common::rng(3);
+
+    let cube = factor::factorise(&(&p * &p * &p), BUDGET, &mut rng);
+    assert_eq!(complete_factors(&cube), vec![p.clone(), p.clone(), p.clone()]);
+    assert!(cube.factors.iter().all(|factor| factor.method == Method::PerfectPower));
+
+    let mut expected = vec![p.clone(), p.clone(), q.clone()];
+    expected.sort();
+    assert_eq!(complete_factors(&factor::factorise(&(&p * &p * &q), BUDGET, &mut rng)), expected);
+}
+
+#[test]
+fn perfect_power_finds_smallest_root() {
+    let p = prime(40, 4);
+    assert_eq!(factor::perfect_power(&p), None);
+    assert_eq!(factor::perfect_power(&(&p * &p)), Some((p.clone(), 2)));
+    assert_eq!(factor::perfect_power(&BigUint::from(1_u32 << 30)), Some((BigUint::from(2_u32), 30)));
+    assert_eq!(factor::perfect_power(&BigUint::from(216_u32)), Some((BigUint::from(6_u32), 3)));
+    for n in 0..4_u32 {
+        assert_eq!(factor::perfect_power(&BigUint::from(n)), None, "{}", n);
+    }
+}
+
+#[test]
+fn ecm_finds_small_factor() {
+    let (p, q) = (prime(40, 5), prime(80, 6));
+    // B1 = 2000 is the GMP-ECM choice for factors of up to 15 digits; p has 12
+    let found = ecm::ecm(&(&p * &q), 2000, 200_000, 100, &mut common::rng(6));
+    assert_eq!(found, Some(p));
+}
+
+#[test]
+fn quadratic_sieve_splits_semiprime() {
+    let (p, q) = (prime(50, 8), prime(50, 9));
+    let found = siqs::quadratic_sieve(&(&p * &q), BUDGET, &mut common::rng(10), |_| {});
+    assert!(found == Some(p.clone()) || found == Some(q.clone()), "found {:?}", found);
+}
+
+#[test]
+fn quadratic_sieve_rejects_unsuitable_numbers() {
+    let mut rng = common::rng(11);
+    let p = prime(50, 12);
+    let too_small = prime(19, 13) * prime(19, 14);
+    let too_big = prime(176, 15) * prime(176, 16);
+    assert!(too_small.bits() < siqs::MIN_SIEVE_BITS && too_big.bits() > siqs::MAX_SIEVE_BITS);
+
+    for n in [prime(100, 17), &p * &p, &p * &p * &p, too_small, too_big].iter() {
+        assert_eq!(siqs::quadratic_sieve(n, BUDGET, &mut rng, |_| {}), None, "{}", n);
+    }
+}
+
+#[test]
+fn factorise_reports_sieve_progress() {
+    let (p, q) = (prime(50, 18), prime(50, 19));
+    let mut reports = Vec::new();
+    let factorisation = factor::factorise_with_progress(&(&p * &q), BUDGET, &mut common::rng(20), |progress| {
+        if let Progress::Sieve(report) = progress {
+            reports.push(report);
+        }
+    });
+
+    let mut expected = vec![p, q];
+    expected.sort();
+    assert_eq!(complete_factors(&factorisation), expected);
+    assert!(factorisation.factors.iter().any(|factor| factor.method == Method::QuadraticSieve));
+    assert!(!reports.is_empty());
+    for pair in reports.windows(2) {
+        assert!(pair[0].elapsed <= pair[1].elapsed && pair[0].polynomials < pair[1].polynomials);
+    }
+    let last = reports.last().unwrap();
+    assert!(last.relations() >= last.target);
+}
+
+// Too slow for a debug build, so only run on request: cargo test --release -- --ignored
+#[test]
+#[ignore]
+fn factorise_splits_200_bit_semiprime_in_15_seconds() {
+    let (p, q) = (prime(100, 21), prime(100, 22));
+    let start = Instant::now();
+    let factorisation = factor::factorise(&(&p * &q), Duration::from_secs(15), &mut common::rng(23));
+
+    let mut expected = vec![p, q];
+    expected.sort();
+    assert_eq!(complete_factors(&factorisation), expected);
+    assert!(start.elapsed() < Duration::from_secs(15));
+}
+
+/// Checks that `factorisation` is complete, with prime factors that multiply to `n`
+fn assert_factors(n: u64, factorisation: &Factorisation) {
+    let factors = complete_factors(factorisation);
+    let mut rng = common::rng(24);
+    assert!(factors.iter().all(|p| primes::is_prime(p, PrimalityTest::BailliePsw, &mut rng)), "{:?}", factors);
+    assert_eq!(factors.iter().product::<BigUint>(), BigUint::from(n));
+}
+
+#[test]
+fn factorise_multiplies_back() {
+    let mut rng = common::rng(25);
+    let p = 1_048_573_u64;
+    let big = [1_000_000_007 * 998_244_353, u64::MAX, p * p];
+    for n in (2..2000).chain(big.iter().cloned()) {
+        assert_factors(n, &factor::factorise(&BigUint::from(n), BUDGET, &mut rng));
+    }
+
+    let factors: Vec<u64> = factor::factorise(&BigUint::from(u64::MAX), BUDGET, &mut rng).factors.iter()
+        .map(|factor| factor.prime.to_u64().unwrap())
+        .collect();
+    assert_eq!(factors, [3, 5, 17, 257, 641, 65537, 6700417]);
+}
+
+#[test]
+fn factorise_handles_edge_inputs() {
+    let mut rng = common::rng(26);
+    let zero = factor::factorise(&BigUint::from(0_u32), BUDGET, &mut rng);
+    assert!(zero.factors.is_empty() && !zero.is_complete());
+    assert_eq!(zero.unfactored, vec![BigUint::from(0_u32)]);
+
+    let one = factor::factorise(&BigUint::from(1_u32), BUDGET, &mut rng);
+    assert!(one.factors.is_empty() && one.is_complete());
+
+    let two = factor::factorise(&BigUint::from(2_u32), BUDGET, &mut rng);
+    assert_eq!(complete_factors(&two), vec![BigUint::from(2_u32)]);
+    assert_eq!(two.factors[0].method, Method::PrimalityTest);
+
+    let four = factor::factorise(&BigUint::from(4_u32), BUDGET, &mut rng);
+    assert_eq!(complete_factors(&four), vec![BigUint::from(2_u32); 2]);
+    assert!(four.factors.iter().all(|factor| factor.method == Method::TrialDivision));
+}
+
+#[test]
+fn cheap_methods_find_factors() {
+    let (p, q) = (BigUint::from(1_000_000_007_u32), BigUint::from(1_000_000_009_u32));
+    let n = &p * &q;
+    assert_eq!(factor::trial_division(&BigUint::from(91_u32), 100), Some(BigUint::from(7_u32)));
+    assert_eq!(factor::trial_division(&BigUint::from(97_u32), 100), None);
+    assert_eq!(factor::trial_division(&n, 1000), None);
+
+    // Twin primes are found on Fermat's first try; a prime has only the trivial a - b = 1
+    assert_eq!(factor::fermat(&n, 1), Some(p.clone()));
+    assert_eq!(factor::fermat(&BigUint::from(101_u32), 100), None);
+
+    let found = factor::pollard_rho(&(&p * BigUint::from(998_244_353_u32)), 1 << 20, &mut common::rng(27));
+    assert!(found == Some(p.clone()) || found == Some(BigUint::from(998_244_353_u32)), "found {:?}", found);
+    assert_eq!(factor::pollard_rho(&BigUint::from(3_u32), 100, &mut common::rng(28)), None);
+
+    // smooth - 1 = 2 * 3 * 37 * 103 * 211 * 281 * 811, and safe - 1 is twice a prime
+    let (smooth, safe) = (BigUint::from(1_099_511_632_867_u64), BigUint::from(1_099_511_628_443_u64));
+    assert_eq!(factor::pollard_p_minus_one(&(&smooth * &safe), 1000), Some(smooth));
+    assert_eq!(factor::pollard_p_minus_one(&(&safe * &safe), 1000), None);
+}
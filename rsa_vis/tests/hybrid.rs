@@ -0,0 +1,58 @@
+extern crate rsa_vis;
+
+mod common;
+
+use rsa_vis::hash::HashAlgorithm;
+use rsa_vis::hybrid::{self, KeyWrap};
+use rsa_vis::rsa::Error;
+
+use common::{key, message};
+
+const WRAPS: [KeyWrap; 4] = [KeyWrap::Kem(HashAlgorithm::Sha256), KeyWrap::Kem(HashAlgorithm::Sha512),
+                             KeyWrap::Oaep(HashAlgorithm::Sha1), KeyWrap::Oaep(HashAlgorithm::Sha256)];
+
+#[test]
+fn containers_round_trip() {
+    let key = key(1024, 1);
+    let mut rng = common::rng(2);
+    for wrap in WRAPS.iter() {
+        for len in [0, 1, 127, 128, 10_000].iter() {
+            let msg = message(*len);
+            let container = hybrid::encrypt(&msg, &key.to_public_key(), *wrap, &mut rng).unwrap();
+            assert!(hybrid::is_container(&container));
+            assert_eq!(hybrid::decrypt(&container, &key), Ok(msg), "{:?}, {} bytes", wrap, len);
+        }
+    }
+}
+
+#[test]
+fn wrong_key_is_rejected() {
+    let (key, other) = (key(1024, 3), key(1024, 4));
+    let mut rng = common::rng(5);
+    for wrap in WRAPS.iter() {
+        let container = hybrid::encrypt(&message(100), &key.to_public_key(), *wrap, &mut rng).unwrap();
+        assert_eq!(hybrid::decrypt(&container, &other), Err(Error::Decryption), "{:?}", wrap);
+    }
+}
+
+#[test]
+fn bad_version_is_rejected() {
+    let key = key(1024, 6);
+    let mut rng = common::rng(7);
+    let mut container = hybrid::encrypt(&message(100), &key.to_public_key(), KeyWrap::default(), &mut rng).unwrap();
+    for version in [0, 9, 255].iter() {
+        container[hybrid::MAGIC.len()] = *version;
+        assert_eq!(hybrid::decrypt(&container, &key), Err(Error::Decryption));
+    }
+}
+
+#[test]
+fn truncated_header_is_rejected() {
+    let key = key(1024, 8);
+    let mut rng = common::rng(9);
+    let container = hybrid::encrypt(b"", &key.to_public_key(), KeyWrap::default(), &mut rng).unwrap();
+    // Cut inside the fixed header, inside the wrapped key, before the nonce, and inside the tag
+    for len in [0, 4, 7, 20, container.len() - 16 - 12, container.len() - 17].iter() {
+        assert_eq!(hybrid::decrypt(&container[..*len], &key), Err(Error::Decryption), "{} bytes", len);
+    }
+}
@@ -0,0 +1,43 @@
+extern crate rsa_vis;
+extern crate serde_json;
+
+mod common;
+
+use std::{env, fs};
+
+use serde_json::Value;
+
+use rsa_vis::io;
+use rsa_vis::rsa;
+
+const MESSAGE: &str = "attack at dawn";
+
+// The key files are always in the current directory, so this is the only test here: tests in one
+// file run on several threads of the same process and would share it
+#[test]
+fn key_files_load_with_and_without_primes() {
+    let dir = env::temp_dir().join(format!("rsa_vis_key_files_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    env::set_current_dir(&dir).unwrap();
+
+    let key = common::key(512, 1);
+    io::write_json_to_disk(&key);
+    let loaded = io::read_key_from_disk().unwrap();
+    assert_eq!(loaded, key);
+
+    // A key saved before the primes and CRT values were written has only d
+    let mut priv_key: Value = serde_json::from_str(&fs::read_to_string("priv_key.txt").unwrap()).unwrap();
+    let fields = priv_key.as_object_mut().unwrap();
+    for field in ["p", "q", "dp", "dq", "qinv"].iter() {
+        assert!(fields.remove(*field).is_some(), "{} was written", field);
+    }
+    fs::write("priv_key.txt", priv_key.to_string()).unwrap();
+
+    let loaded = io::read_key_from_disk().unwrap();
+    assert_eq!((&loaded.n, &loaded.e, &loaded.d), (&key.n, &key.e, &key.d));
+    assert!(loaded.p.is_none() && loaded.q.is_none() && loaded.crt.is_none());
+    let cipher = rsa::encrypt_str(MESSAGE, &loaded.to_public_key()).unwrap();
+    assert_eq!(rsa::decrypt_str(&cipher, &loaded).unwrap(), MESSAGE);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,49 @@
+extern crate rsa_vis;
+
+mod common;
+
+use rsa_vis::hash::HashAlgorithm;
+use rsa_vis::pkcs1;
+use rsa_vis::rsa::Error;
+
+use common::key_and_rng;
+
+#[test]
+fn oaep_round_trips() {
+    let (key, mut rng) = key_and_rng(3);
+    let pubkey = key.to_public_key();
+    for algorithm in [HashAlgorithm::Sha1, HashAlgorithm::Sha256, HashAlgorithm::Sha384].iter() {
+        let mut digest = algorithm.hasher();
+        let max_len = pkcs1::oaep_max_message_len(&pubkey, digest.as_ref());
+        for len in [0, 1, max_len].iter() {
+            let msg = vec![0x5a_u8; *len];
+            let cipher = pkcs1::encrypt_oaep(&msg, &pubkey, digest.as_mut(), b"label", &mut rng).unwrap();
+            assert_eq!(cipher.len(), key.size());
+            assert_eq!(pkcs1::decrypt_oaep(&cipher, &key, digest.as_mut(), b"label"), Ok(msg), "{:?}", algorithm);
+        }
+        let too_long = vec![0_u8; max_len + 1];
+        assert_eq!(pkcs1::encrypt_oaep(&too_long, &pubkey, digest.as_mut(), &[], &mut rng), Err(Error::MessageTooLong));
+    }
+}
+
+#[test]
+fn oaep_rejects_wrong_label() {
+    let (key, mut rng) = key_and_rng(3);
+    let mut digest = HashAlgorithm::Sha256.hasher();
+    let cipher = pkcs1::encrypt_oaep(b"message", &key.to_public_key(), digest.as_mut(), b"label", &mut rng).unwrap();
+    assert_eq!(pkcs1::decrypt_oaep(&cipher, &key, digest.as_mut(), b"other"), Err(Error::Decryption));
+    assert_eq!(pkcs1::decrypt_oaep(&cipher, &key, digest.as_mut(), &[]), Err(Error::Decryption));
+}
+
+#[test]
+fn oaep_rejects_altered_cipher() {
+    let (key, mut rng) = key_and_rng(3);
+    let mut digest = HashAlgorithm::Sha256.hasher();
+    let cipher = pkcs1::encrypt_oaep(b"message", &key.to_public_key(), digest.as_mut(), &[], &mut rng).unwrap();
+    for i in [0, 1, cipher.len() / 2, cipher.len() - 1].iter() {
+        let mut altered = cipher.clone();
+        altered[*i] ^= 0x40;
+        assert_eq!(pkcs1::decrypt_oaep(&altered, &key, digest.as_mut(), &[]), Err(Error::Decryption), "byte {}", i);
+    }
+    assert_eq!(pkcs1::decrypt_oaep(&cipher[1..], &key, digest.as_mut(), &[]), Err(Error::Decryption));
+}
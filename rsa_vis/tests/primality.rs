@@ -0,0 +1,89 @@
+extern crate num;
+extern crate rsa_vis;
+
+use num::BigUint;
+use num::traits::One;
+
+use rsa_vis::primes::{self, PrimalityTest};
+use rsa_vis::rand;
+
+const LIMIT: usize = 32_000;
+
+/// Strong Lucas pseudoprimes below `LIMIT` (OEIS A217255)
+const LUCAS_PSEUDOPRIMES: [usize; 8] = [5459, 5777, 10877, 16109, 18971, 22499, 24569, 25199];
+
+const ALL_TESTS: [PrimalityTest; 4] = [PrimalityTest::MillerRabin(8), PrimalityTest::DeterministicMillerRabin,
+                                       PrimalityTest::StrongLucas, PrimalityTest::BailliePsw];
+
+/// Returns whether each number below `LIMIT` is prime, according to `sieve`
+fn sieve_table(sieve: &[u32]) -> Vec<bool> {
+    let mut table = vec![false; LIMIT];
+    for p in sieve.iter().filter(|p| (**p as usize) < LIMIT) {
+        table[*p as usize] = true;
+    }
+    table
+}
+
+#[test]
+fn tests_agree_with_sieves() {
+    let mut rng = rand::new_seed(1);
+    let eratosthenes = sieve_table(&primes::sieve_of_eratosthenes(LIMIT));
+    let atkin = sieve_table(&primes::sieve_of_atkin(LIMIT));
+    assert_eq!(eratosthenes, atkin);
+
+    for test in ALL_TESTS.iter() {
+        for (n, prime) in eratosthenes.iter().enumerate() {
+            let expected = *prime || (*test == PrimalityTest::StrongLucas && LUCAS_PSEUDOPRIMES.contains(&n));
+            assert_eq!(primes::is_prime(&BigUint::from(n), *test, &mut rng), expected, "{} with {:?}", n, test);
+        }
+    }
+}
+
+#[test]
+fn pseudoprimes_are_caught() {
+    let mut rng = rand::new_seed(2);
+    // Strong pseudoprimes to base 2, and one to every base up to 37 except 29, 31 and 37
+    for n in [2047_u64, 3277, 4033, 4681, 8321, 3215031751, 3825123056546413051].iter() {
+        let n = BigUint::from(*n);
+        assert!(!primes::is_prime(&n, PrimalityTest::DeterministicMillerRabin, &mut rng));
+        assert!(!primes::is_prime(&n, PrimalityTest::BailliePsw, &mut rng));
+    }
+
+    // Strong Lucas pseudoprimes, which Miller-Rabin to base 2 rejects
+    for n in LUCAS_PSEUDOPRIMES.iter() {
+        let n = BigUint::from(*n);
+        assert!(primes::is_prime(&n, PrimalityTest::StrongLucas, &mut rng));
+        assert!(!primes::is_prime(&n, PrimalityTest::BailliePsw, &mut rng));
+    }
+}
+
+#[test]
+fn large_numbers() {
+    let mut rng = rand::new_seed(3);
+    let one = BigUint::one();
+    let m127 = (&one << 127) - &one;
+    let m521 = (&one << 521) - &one;
+    let m61 = (&one << 61) - &one;
+    for test in ALL_TESTS.iter() {
+        assert!(primes::is_prime(&m61, *test, &mut rng));
+        assert!(primes::is_prime(&m127, *test, &mut rng));
+        assert!(primes::is_prime(&m521, *test, &mut rng));
+        assert!(!primes::is_prime(&(&m127 * &m61), *test, &mut rng));
+        assert!(!primes::is_prime(&(&m127 * &m127), *test, &mut rng));
+        assert!(!primes::is_prime(&((&one << 128) - &one), *test, &mut rng));
+    }
+}
+
+#[test]
+fn generated_primes_pass_every_test() {
+    let mut rng = rand::new_seed(4);
+    for test in ALL_TESTS.iter() {
+        for bits in [2, 3, 16, 64, 65, 256].iter() {
+            let p = primes::gen_prime(*bits, *test, &mut rng);
+            assert_eq!(p.bits(), *bits);
+            for check in ALL_TESTS.iter() {
+                assert!(primes::is_prime(&p, *check, &mut rng), "{} from {:?} failed {:?}", p, test, check);
+            }
+        }
+    }
+}
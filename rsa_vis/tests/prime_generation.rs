@@ -0,0 +1,157 @@
+extern crate num;
+extern crate rsa_vis;
+
+mod common;
+
+use std::{env, fs, process};
+use std::time::Duration;
+
+use num::{BigUint, Integer};
+use num::traits::One;
+
+use rsa_vis::factor;
+use rsa_vis::io;
+use rsa_vis::primes::{self, PrimalityTest, PrimeCertificate};
+use rsa_vis::rand::ChaChaRng;
+use rsa_vis::rsa::{Error, KeyBuilder, PrimeMethod};
+
+/// Returns certificates for RSA primes of 256 and 512 bits, each with several steps
+fn certificates() -> Vec<PrimeCertificate> {
+    let mut rng = common::rng(1);
+    [256, 512].iter().map(|bits| primes::gen_provable_rsa_prime(*bits, &mut rng)).collect()
+}
+
+#[test]
+fn generated_certificates_verify() {
+    let certificates = certificates();
+    for (certificate, bits) in certificates.iter().zip([256, 512].iter()) {
+        assert!(certificate.steps.len() >= 3);
+        assert_eq!(certificate.prime().bits(), *bits);
+        assert!(primes::verify_certificate(certificate));
+    }
+
+    let path = env::temp_dir().join(format!("rsa_vis_certificates_{}.json", process::id()));
+    let path = path.to_str().unwrap();
+    io::write_certificates_to_disk(&certificates, path).unwrap();
+    let read = io::read_certificates_from_disk(path).unwrap();
+    fs::remove_file(path).unwrap();
+    assert_eq!(read, certificates);
+    assert!(read.iter().all(primes::verify_certificate));
+}
+
+#[test]
+fn altered_certificates_are_rejected() {
+    let one: BigUint = One::one();
+    let certificate = certificates().pop().unwrap();
+    let altered = |change: &dyn Fn(&mut PrimeCertificate)| {
+        let mut altered = certificate.clone();
+        change(&mut altered);
+        !primes::verify_certificate(&altered)
+    };
+
+    // Base: composite, or prime but not dividing the first step's prime - 1
+    assert!(altered(&|c| c.base += 1));
+    let is_prime = |n: &u32| (2..).take_while(|d| d * d <= *n).all(|d| !(*n).is_multiple_of(d));
+    let next_prime = (certificate.base + 1..).find(is_prime).unwrap();
+    assert!(altered(&|c| c.base = next_prime));
+
+    // Step primes: each is off by two, which the previous prime no longer divides one less than
+    for i in 0..certificate.steps.len() {
+        assert!(altered(&|c| c.steps[i].prime += 2_u32), "step {}", i);
+    }
+
+    // Witnesses: almost any other witness would prove the same prime, so these are ones that can't,
+    // out of range or a power w^q with w^((p - 1) / q) = 1 mod p
+    for i in 0..certificate.steps.len() {
+        let factor = if i == 0 { BigUint::from(certificate.base) } else { certificate.steps[i - 1].prime.clone() };
+        let prime = certificate.steps[i].prime.clone();
+        let power = certificate.steps[i].witness.modpow(&factor, &prime);
+        assert!(altered(&|c| c.steps[i].witness = one.clone()), "step {}", i);
+        assert!(altered(&|c| c.steps[i].witness = &prime - &one), "step {}", i);
+        assert!(altered(&|c| c.steps[i].witness = power.clone()), "step {}", i);
+    }
+
+    // Order: every step has to follow from the one before
+    assert!(altered(&|c| c.steps.swap(0, 1)));
+    assert!(altered(&|c| c.steps.reverse()));
+    assert!(altered(&|c| {
+        c.steps.remove(0);
+    }));
+}
+
+#[test]
+fn fips_primes_meet_a_1_3_conditions() {
+    let mut rng = common::rng(2);
+    let one: BigUint = One::one();
+    let e = BigUint::from(65_537_u32);
+    for nlen in [1024, 2048].iter() {
+        let half = nlen / 2;
+        let pair = primes::gen_fips_rsa_primes(*nlen, &e, &mut rng).unwrap();
+        for prime in [&pair.p, &pair.q].iter() {
+            assert_eq!(prime.bits(), half);
+            assert_eq!(*prime >> (half - 2), BigUint::from(3_u32), "top bits of {}", prime);
+            assert!(((*prime - &one).gcd(&e)).is_one());
+            assert!(primes::is_prime(prime, PrimalityTest::BailliePsw, &mut rng));
+        }
+        let distance = if pair.p > pair.q { &pair.p - &pair.q } else { &pair.q - &pair.p };
+        assert!(distance > &one << (half - 100));
+        assert_eq!((&pair.p * &pair.q).bits(), *nlen);
+        assert!(pair.p_candidates >= 1 && pair.p_candidates <= 5 * half);
+        assert!(pair.q_candidates >= 1 && pair.q_candidates <= 10 * half);
+    }
+
+    for nlen in [0, 2, 3, 1023, 1025].iter() {
+        assert!(primes::gen_fips_rsa_primes(*nlen, &e, &mut rng).is_none(), "{} bits", nlen);
+    }
+    // e must be odd and more than 16 bits long
+    assert!(primes::gen_fips_rsa_primes(1024, &BigUint::from(3_u32), &mut rng).is_none());
+    assert!(primes::gen_fips_rsa_primes(1024, &BigUint::from(65_536_u32), &mut rng).is_none());
+}
+
+#[test]
+fn safe_primes_are_twice_a_prime_plus_one() {
+    let mut rng = common::rng(3);
+    for bits in [16, 64, 256].iter() {
+        for p in [primes::gen_safe_prime(*bits, &mut rng), primes::gen_safe_rsa_prime(*bits, &mut rng)].iter() {
+            assert_eq!(p.bits(), *bits);
+            assert!(primes::is_prime(p, PrimalityTest::BailliePsw, &mut rng));
+            assert!(primes::is_prime(&(p >> 1), PrimalityTest::BailliePsw, &mut rng), "{} - 1 is not twice a prime", p);
+        }
+    }
+}
+
+#[test]
+fn strong_primes_have_large_factors_either_side() {
+    let mut rng = common::rng(4);
+    let has_factor_of = |n: &BigUint, bits: usize, rng: &mut ChaChaRng| {
+        let factorisation = factor::factorise(n, Duration::from_secs(30), rng);
+        assert!(factorisation.is_complete(), "couldn't factor {}", n);
+        factorisation.factors.iter().any(|factor| factor.prime.bits() == bits)
+    };
+
+    // Below 512 bits, the auxiliary primes are a quarter of the length
+    for bits in [primes::MIN_STRONG_PRIME_BITS, 64, 96].iter() {
+        for p in [primes::gen_strong_prime(*bits, &mut rng), primes::gen_strong_rsa_prime(*bits, &mut rng)].iter() {
+            assert_eq!(p.bits(), *bits);
+            assert!(primes::is_prime(p, PrimalityTest::BailliePsw, &mut rng));
+            assert!(has_factor_of(&(p - 1_u32), bits / 4, &mut rng), "p - 1 for p = {}", p);
+            assert!(has_factor_of(&(p + 1_u32), bits / 4, &mut rng), "p + 1 for p = {}", p);
+        }
+    }
+}
+
+#[test]
+fn safe_and_strong_keys_need_two_minimum_primes() {
+    let mut rng = common::rng(5);
+    let min_bits = 2 * primes::MIN_STRONG_PRIME_BITS;
+    for method in [PrimeMethod::Safe, PrimeMethod::Strong].iter() {
+        for bits in [40, min_bits - 1].iter() {
+            let builder = KeyBuilder::new().bits(*bits).prime_method(*method);
+            assert_eq!(builder.build(&mut rng).err(), Some(Error::InvalidParameters), "{:?}, {} bits", method, bits);
+        }
+        let key = KeyBuilder::new().bits(min_bits).prime_method(*method).build(&mut rng).unwrap();
+        assert_eq!(key.n.bits(), min_bits);
+    }
+    // Random primes can be that small
+    assert!(KeyBuilder::new().bits(min_bits - 1).build(&mut rng).is_ok());
+}
@@ -0,0 +1,97 @@
+extern crate num;
+extern crate rsa_vis;
+
+mod common;
+
+use std::{env, fs, process};
+
+use num::{BigInt, BigUint, Integer};
+use num::bigint::Sign;
+use num::traits::One;
+
+use rsa_vis::io;
+use rsa_vis::rsa::{self, CrtParams, Error, RsaPrivateKey, RsaPublicKey};
+
+/// Returns `key` without its primes and CRT values, as read from an old key file, with private
+/// exponent `d`
+fn without_primes(key: &RsaPrivateKey, d: BigUint) -> RsaPrivateKey {
+    RsaPrivateKey::new(key.n.clone(), key.e.clone(), d, None, None)
+}
+
+#[test]
+fn with_primes_restores_stripped_key() {
+    let mut rng = common::rng(1);
+    for (bits, seed) in [(64, 2), (512, 3), (1024, 4)].iter() {
+        let key = common::key(*bits, *seed);
+        let (p, q) = (key.p.clone().unwrap(), key.q.clone().unwrap());
+        let (p, q) = if p > q { (p, q) } else { (q, p) };
+
+        let recovered = without_primes(&key, key.d.clone()).with_primes(&mut rng).unwrap();
+        let (n, e, d) = (key.n.clone(), key.e.clone(), key.d.clone());
+        let expected = RsaPrivateKey::new(n, e, d, Some(p.clone()), Some(q.clone()));
+        assert_eq!(recovered, expected, "{} bits", bits);
+        assert_eq!(rsa::recover_primes(&key.n, &key.e, &key.d, &mut rng), Some((p, q)));
+
+        // A key that already has its primes is returned as it is
+        assert_eq!(key.with_primes(&mut rng), Ok(key.clone()));
+    }
+}
+
+#[test]
+fn with_primes_rejects_mismatched_d() {
+    let mut rng = common::rng(5);
+    let key = common::key(512, 6);
+    // d + 1 makes de - 1 odd, and d + 2 leaves it even but no multiple of lambda(n)
+    for wrong in [&key.d + 1_u32, &key.d + 2_u32, BigUint::from(3_u32)].iter() {
+        assert_eq!(without_primes(&key, wrong.clone()).with_primes(&mut rng), Err(Error::InvalidKey));
+    }
+}
+
+#[test]
+fn with_primes_accepts_d_mod_phi() {
+    let mut rng = common::rng(7);
+    let one: BigUint = One::one();
+    let d_phi = |key: &RsaPrivateKey| {
+        let (p, q) = (key.p.as_ref().unwrap(), key.q.as_ref().unwrap());
+        let phi = BigInt::from_biguint(Sign::Plus, (p - &one) * (q - &one));
+        let inverse = BigInt::from_biguint(Sign::Plus, key.e.clone()).extended_gcd(&phi).x;
+        inverse.mod_floor(&phi).to_biguint().unwrap()
+    };
+
+    // The textbook d, the inverse of e mod phi(n) = (p - 1)(q - 1), is often the same number as the
+    // inverse mod lambda(n), so take a key where it isn't
+    let key = (8..).map(|seed| common::key(512, seed)).find(|key| d_phi(key) != key.d).unwrap();
+    let recovered = without_primes(&key, d_phi(&key)).with_primes(&mut rng).unwrap();
+
+    let (p, q) = (key.p.clone().unwrap(), key.q.clone().unwrap());
+    let (p, q) = if p > q { (p, q) } else { (q, p) };
+    // Both d agree mod p - 1 and q - 1, so the CRT values are the same too
+    assert_eq!(recovered.crt, Some(CrtParams::new(&key.d, &p, &q)));
+    assert_eq!((recovered.p, recovered.q), (Some(p), Some(q)));
+}
+
+#[test]
+fn encrypt_needs_two_byte_modulus() {
+    // 187 = 11 * 17 is one byte long, which leaves no room for a block one byte shorter
+    let tiny = RsaPublicKey::new(BigUint::from(187_u32), BigUint::from(3_u32));
+    assert_eq!(tiny.size(), 1);
+    assert_eq!(rsa::encrypt(&[], &tiny), Err(Error::KeyTooSmall));
+    assert_eq!(rsa::encrypt_str("a", &tiny), Err(Error::KeyTooSmall));
+
+    // An empty file fits in one block, so it reaches `encrypt` rather than the stream
+    let dir = env::temp_dir();
+    let src = dir.join(format!("rsa_vis_empty_{}", process::id()));
+    let dest = dir.join(format!("rsa_vis_empty_{}.json", process::id()));
+    fs::write(&src, []).unwrap();
+    let result = io::encrypt_file(src.to_str().unwrap(), dest.to_str().unwrap(), &tiny);
+    fs::remove_file(&src).unwrap();
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    assert!(!dest.exists());
+
+    // 3233 = 61 * 53 is two bytes long, so each block holds a single byte
+    let small = RsaPrivateKey::new(BigUint::from(3233_u32), BigUint::from(17_u32), BigUint::from(2753_u32), None, None);
+    for msg in [&b""[..], b"\0", b"abc"].iter() {
+        let cipher = rsa::encrypt(msg, &small.to_public_key()).unwrap();
+        assert_eq!(rsa::decrypt(&cipher, &small).unwrap(), *msg);
+    }
+}
@@ -0,0 +1,124 @@
+extern crate num;
+extern crate rsa_vis;
+
+use num::BigUint;
+
+use rsa_vis::primes::{self, PrimalityTest};
+use rsa_vis::rand::{self, RandomSource};
+
+const LIMIT: usize = 3_000_000;
+
+/// Number of primes up to `LIMIT`
+const PRIMES_BELOW_LIMIT: usize = 216_816;
+
+/// Slow but obviously correct primality test
+fn trial_division(n: u32) -> bool {
+    n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d))
+}
+
+/// Primes up to `LIMIT` from the segmented sieve, which both simple sieves are checked against
+fn reference() -> Vec<u32> {
+    primes::primes_in_range(0, LIMIT as u64 + 1).map(|p| p as u32).collect()
+}
+
+#[test]
+fn small_limits_match_trial_division() {
+    for limit in 0..=1000 {
+        let expected: Vec<u32> = (0..=limit).filter(|n| trial_division(*n)).collect();
+        assert_eq!(primes::sieve_of_eratosthenes(limit as usize), expected, "Eratosthenes up to {}", limit);
+        assert_eq!(primes::sieve_of_atkin(limit as usize), expected, "Atkin up to {}", limit);
+    }
+}
+
+#[test]
+fn squares_of_primes_are_excluded() {
+    for p in primes::sieve_of_eratosthenes(100) {
+        let square = (p * p) as usize;
+        assert!(primes::sieve_of_eratosthenes(square).iter().all(|n| *n < p * p), "{} in Eratosthenes", square);
+        assert!(primes::sieve_of_atkin(square).iter().all(|n| *n < p * p), "{} in Atkin", square);
+    }
+}
+
+#[test]
+fn sieves_agree_up_to_limit() {
+    let expected = reference();
+    assert_eq!(expected.len(), PRIMES_BELOW_LIMIT);
+    assert_eq!(primes::sieve_of_eratosthenes(LIMIT), expected);
+    assert_eq!(primes::sieve_of_atkin(LIMIT), expected);
+    assert_eq!(primes::count_primes(0, LIMIT as u64 + 1), PRIMES_BELOW_LIMIT as u64);
+}
+
+#[test]
+fn random_limits_are_inclusive() {
+    let expected = reference();
+    let mut rng = rand::new_seed(5);
+    for _ in 0..10 {
+        let limit = rng.gen_range(0, LIMIT as u64) as usize;
+        let below: Vec<u32> = expected.iter().cloned().take_while(|p| *p as usize <= limit).collect();
+        assert_eq!(primes::sieve_of_eratosthenes(limit), below, "Eratosthenes up to {}", limit);
+        assert_eq!(primes::sieve_of_atkin(limit), below, "Atkin up to {}", limit);
+    }
+
+    // Limits that are themselves prime must be included
+    for limit in [2_usize, 3, 5, 7, 2_999_999].iter() {
+        assert_eq!(primes::sieve_of_eratosthenes(*limit).last(), Some(&(*limit as u32)));
+        assert_eq!(primes::sieve_of_atkin(*limit).last(), Some(&(*limit as u32)));
+    }
+}
+
+#[test]
+fn miller_rabin_agrees_with_sieve() {
+    let expected = reference();
+    let mut rng = rand::new_seed(6);
+    for _ in 0..5000 {
+        let n = rng.gen_range(0, LIMIT as u64 + 1) as u32;
+        let is_prime = expected.binary_search(&n).is_ok();
+        let n = BigUint::from(n);
+        assert_eq!(primes::_test_miller_rabin(&n, 20, &mut rng), is_prime, "{}", n);
+        assert_eq!(primes::is_prime(&n, PrimalityTest::BailliePsw, &mut rng), is_prime, "{}", n);
+    }
+
+    // Every prime near the limit passes
+    for p in expected.iter().rev().take(500) {
+        assert!(primes::_test_miller_rabin(&BigUint::from(*p), 20, &mut rng), "{}", p);
+    }
+}
+
+#[test]
+fn ranges_match_reference_from_any_start() {
+    let expected = reference();
+    let in_range = |lo: u64, hi: u64| -> Vec<u64> {
+        expected.iter().map(|p| u64::from(*p)).filter(|p| lo <= *p && *p < hi).collect()
+    };
+
+    // Bounds on either side of multiples of 30, and on the wheel primes themselves
+    let edges = [(1, 2), (2, 3), (2, 6), (3, 5), (5, 8), (7, 8), (29, 31), (30, 31), (31, 61), (59, 91), (100, 100)];
+    let mut rng = rand::new_seed(7);
+    let random: Vec<(u64, u64)> = (0..50).map(|_| {
+        let lo = rng.gen_range(1, LIMIT as u64);
+        (lo, lo + rng.gen_range(0, 1_000_000).min(LIMIT as u64 + 1 - lo))
+    }).collect();
+    for (lo, hi) in edges.iter().chain(random.iter()) {
+        let primes: Vec<u64> = primes::primes_in_range(*lo, *hi).collect();
+        assert_eq!(primes, in_range(*lo, *hi), "[{}, {})", lo, hi);
+        assert_eq!(primes::count_primes(*lo, *hi), primes.len() as u64, "[{}, {})", lo, hi);
+    }
+
+    // An empty or backwards range has no primes
+    assert_eq!(primes::primes_in_range(1000, 10).next(), None);
+    assert_eq!(primes::count_primes(1000, 10), 0);
+}
+
+#[test]
+fn window_near_2_pow_40_matches_trial_division() {
+    // Every composite below 2^40 + 2^20 has a prime factor of at most 2^20 + 1, so these settle it
+    let divisors: Vec<u64> = primes::sieve_of_eratosthenes((1 << 20) + 1).iter().map(|p| u64::from(*p)).collect();
+    let is_prime = |n: u64| divisors.iter().take_while(|d| *d * *d <= n).all(|d| !n.is_multiple_of(*d));
+
+    for (lo, hi) in [((1 << 40) - 10_007, (1 << 40) + 9_973), ((1 << 40) + 13, (1 << 40) + 14)].iter() {
+        let expected: Vec<u64> = (*lo..*hi).filter(|n| is_prime(*n)).collect();
+        let primes: Vec<u64> = primes::primes_in_range(*lo, *hi).collect();
+        assert_eq!(primes, expected, "[{}, {})", lo, hi);
+        assert_eq!(primes::count_primes(*lo, *hi), expected.len() as u64);
+    }
+}
@@ -0,0 +1,105 @@
+extern crate rsa_vis;
+
+mod common;
+
+use rsa_vis::hash::HashAlgorithm;
+use rsa_vis::rsa::{self, Error, RsaPrivateKey};
+use rsa_vis::signature;
+
+use common::key_and_rng;
+
+const MESSAGE: &[u8] = b"release v1.0";
+
+/// The two signature schemes, verified with a 32-byte salt for PSS
+#[derive(Clone, Copy, Debug)]
+enum Scheme {
+    Pkcs1v15,
+    Pss,
+}
+
+/// Verifies `sig` of `msg` under `key` with `scheme` and `hash`
+fn verify(scheme: Scheme, msg: &[u8], sig: &[u8], key: &RsaPrivateKey, hash: HashAlgorithm) -> Result<(), Error> {
+    match scheme {
+        Scheme::Pkcs1v15 => signature::verify_pkcs1v15(msg, sig, &key.to_public_key(), hash),
+        Scheme::Pss => signature::verify_pss(msg, sig, &key.to_public_key(), hash, 32),
+    }
+}
+
+/// Returns a key, and a signature of `MESSAGE` with SHA-256 under each scheme
+fn signatures() -> (RsaPrivateKey, Vec<(Scheme, Vec<u8>)>) {
+    let (key, mut rng) = key_and_rng(4);
+    let pkcs1 = signature::sign_pkcs1v15(MESSAGE, &key, HashAlgorithm::Sha256).unwrap();
+    let pss = signature::sign_pss(MESSAGE, &key, HashAlgorithm::Sha256, 32, &mut rng).unwrap();
+    (key, vec![(Scheme::Pkcs1v15, pkcs1), (Scheme::Pss, pss)])
+}
+
+#[test]
+fn signatures_verify() {
+    let (key, schemes) = signatures();
+    for (scheme, sig) in &schemes {
+        assert_eq!(verify(*scheme, MESSAGE, sig, &key, HashAlgorithm::Sha256), Ok(()));
+    }
+}
+
+#[test]
+fn wrong_length_is_rejected() {
+    let (key, schemes) = signatures();
+    for (scheme, sig) in &schemes {
+        let mut longer = vec![0_u8];
+        longer.extend(sig);
+        for malformed in [&sig[1..], &longer[..], &[][..]].iter() {
+            assert_eq!(verify(*scheme, MESSAGE, malformed, &key, HashAlgorithm::Sha256), Err(Error::Verification));
+        }
+    }
+}
+
+#[test]
+fn signature_not_below_modulus_is_rejected() {
+    let (key, schemes) = signatures();
+    let n = rsa::i2osp(&key.n, key.size()).unwrap();
+    let all_ones = vec![0xff_u8; key.size()];
+    for (scheme, _) in &schemes {
+        assert_eq!(verify(*scheme, MESSAGE, &n, &key, HashAlgorithm::Sha256), Err(Error::Verification));
+        assert_eq!(verify(*scheme, MESSAGE, &all_ones, &key, HashAlgorithm::Sha256), Err(Error::Verification));
+    }
+}
+
+#[test]
+fn wrong_hash_is_rejected() {
+    let (key, schemes) = signatures();
+    for (scheme, sig) in &schemes {
+        for hash in [HashAlgorithm::Sha1, HashAlgorithm::Sha384, HashAlgorithm::Sha512].iter() {
+            assert_eq!(verify(*scheme, MESSAGE, sig, &key, *hash), Err(Error::Verification), "{:?}", hash);
+        }
+    }
+}
+
+#[test]
+fn altered_message_or_signature_is_rejected() {
+    let (key, schemes) = signatures();
+    for (scheme, sig) in &schemes {
+        assert_eq!(verify(*scheme, b"release v1.1", sig, &key, HashAlgorithm::Sha256), Err(Error::Verification));
+        for i in [0, sig.len() / 2, sig.len() - 1].iter() {
+            let mut altered = sig.clone();
+            altered[*i] ^= 1;
+            assert_eq!(verify(*scheme, MESSAGE, &altered, &key, HashAlgorithm::Sha256), Err(Error::Verification));
+        }
+    }
+}
+
+#[test]
+fn oversized_pss_salt_is_rejected() {
+    let (key, mut rng) = key_and_rng(4);
+    // A 1024-bit key leaves 128 - 32 - 2 = 94 bytes for the salt with SHA-256
+    assert!(signature::sign_pss(MESSAGE, &key, HashAlgorithm::Sha256, 94, &mut rng).is_ok());
+    for salt_len in [95, usize::MAX].iter() {
+        assert_eq!(signature::sign_pss(MESSAGE, &key, HashAlgorithm::Sha256, *salt_len, &mut rng),
+                   Err(Error::KeyTooSmall));
+    }
+
+    let sig = signature::sign_pss(MESSAGE, &key, HashAlgorithm::Sha256, 32, &mut rng).unwrap();
+    for salt_len in [95, 1000, usize::MAX].iter() {
+        assert_eq!(signature::verify_pss(MESSAGE, &sig, &key.to_public_key(), HashAlgorithm::Sha256, *salt_len),
+                   Err(Error::Verification), "salt length {}", salt_len);
+    }
+}
@@ -0,0 +1,74 @@
+extern crate rsa_vis;
+
+mod common;
+
+use std::io;
+
+use rsa_vis::aead;
+use rsa_vis::hybrid::KeyWrap;
+use rsa_vis::rsa::RsaPrivateKey;
+use rsa_vis::stream::{self, CHUNK_SIZE};
+
+use common::{key, message};
+
+/// Length of a sealed full chunk
+const SEALED_CHUNK_SIZE: usize = CHUNK_SIZE + aead::TAG_SIZE;
+
+/// Returns `msg` streamed for `key`, and the length of the container's header
+fn encrypt(msg: &[u8], key: &RsaPrivateKey) -> (Vec<u8>, usize) {
+    let mut container = Vec::new();
+    let mut rng = common::rng(msg.len() as u8);
+    stream::encrypt_stream(msg, &mut container, &key.to_public_key(), KeyWrap::default(), &mut rng).unwrap();
+
+    // Every chunk but the last is full, and the last holds at least one byte unless the message is empty
+    let chunks = msg.len().div_ceil(CHUNK_SIZE).max(1);
+    let header_len = container.len() - (msg.len() + chunks * aead::TAG_SIZE);
+    (container, header_len)
+}
+
+/// Returns the message decrypted from `container`
+fn decrypt(container: &[u8], key: &RsaPrivateKey) -> io::Result<Vec<u8>> {
+    let mut msg = Vec::new();
+    stream::decrypt_stream(container, &mut msg, key)?;
+    Ok(msg)
+}
+
+#[test]
+fn streams_round_trip() {
+    let key = key(1024, 1);
+    for len in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, 2 * CHUNK_SIZE].iter() {
+        let msg = message(*len);
+        let (container, _) = encrypt(&msg, &key);
+        assert_eq!(decrypt(&container, &key).unwrap(), msg, "{} bytes", len);
+    }
+}
+
+#[test]
+fn empty_stream_is_one_empty_chunk() {
+    let key = key(1024, 2);
+    let (container, header_len) = encrypt(&[], &key);
+    assert_eq!(container.len(), header_len + aead::TAG_SIZE);
+    assert!(decrypt(&container, &key).unwrap().is_empty());
+}
+
+#[test]
+fn stream_cut_at_chunk_boundary_is_rejected() {
+    let key = key(1024, 3);
+    let (container, header_len) = encrypt(&message(3 * CHUNK_SIZE), &key);
+    for chunks in 0..3 {
+        let cut = &container[..header_len + chunks * SEALED_CHUNK_SIZE];
+        let error = decrypt(cut, &key).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "cut after {} chunks", chunks);
+    }
+}
+
+#[test]
+fn swapped_chunks_are_rejected() {
+    let key = key(1024, 4);
+    let (mut container, header_len) = encrypt(&message(3 * CHUNK_SIZE), &key);
+    let (first, second) = container[header_len..].split_at_mut(SEALED_CHUNK_SIZE);
+    first.swap_with_slice(&mut second[..SEALED_CHUNK_SIZE]);
+
+    let error = decrypt(&container, &key).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}